@@ -0,0 +1,340 @@
+use crate::clock::TimeOfDay;
+use crate::config::SpiConfig;
+use crate::error::Result;
+use chrono::NaiveTime;
+use rppal::spi::Spi;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// The fastest the MCP3008 can be clocked, when powered from 5V
+const MCP3008_MAX_CLOCK_SPEED: u32 = 3_600_000;
+
+/// How often to read the light sensor, the ring is redrawn far more often than the room changes
+const SENSOR_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much of the ambient level from a second ago carries over, so a passing shadow or a noisy
+/// ADC doesn't make the ring flicker
+const AMBIENT_SMOOTHING: f64 = 0.7;
+
+/// An MCP3008 ADC with a light dependent resistor on one of its channels
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorConfig {
+    pub enabled: bool,
+    pub bus: u8,
+    pub slave_select: u8,
+    pub clock_speed: u32,
+    pub channel: u8,
+    /// How much of the scheduled level to keep in complete darkness
+    pub dark_level: f64,
+}
+
+impl SensorConfig {
+    /// Where the ADC is on SPI
+    pub fn spi(&self) -> SpiConfig {
+        SpiConfig {
+            bus: self.bus,
+            slave_select: self.slave_select,
+            clock_speed: self.clock_speed,
+        }
+    }
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        SensorConfig {
+            enabled: false,
+            bus: 0,
+            slave_select: 0,
+            clock_speed: 1_000_000,
+            channel: 0,
+            dark_level: 0.2,
+        }
+    }
+}
+
+/// How bright the whole ring is, from 0.0 to 1.0, by day and by night
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrightnessConfig {
+    pub level: f64,
+    pub night_level: f64,
+    pub night_start: TimeOfDay,
+    pub night_end: TimeOfDay,
+    pub sensor: SensorConfig,
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        BrightnessConfig {
+            level: 1.0,
+            night_level: 0.3,
+            night_start: TimeOfDay(NaiveTime::from_hms(22, 0, 0)),
+            night_end: TimeOfDay(NaiveTime::from_hms(7, 0, 0)),
+            sensor: SensorConfig::default(),
+        }
+    }
+}
+
+impl BrightnessConfig {
+    /// Whether `now` falls in the night, which may run past midnight
+    pub fn is_night(&self, now: NaiveTime) -> bool {
+        let (start, end) = (self.night_start.0, self.night_end.0);
+        if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+
+    pub fn scheduled_level(&self, now: NaiveTime) -> f64 {
+        if self.is_night(now) {
+            self.night_level
+        } else {
+            self.level
+        }
+    }
+
+    /// Problems with the settings, for config validation
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        for (name, value) in [
+            ("level", self.level),
+            ("night_level", self.night_level),
+            ("sensor.dark_level", self.sensor.dark_level),
+        ]
+        .iter()
+        {
+            if !(0.0..=1.0).contains(value) {
+                problems.push(format!("brightness.{} must be between 0.0 and 1.0", name));
+            }
+        }
+        if self.sensor.channel > 7 {
+            problems.push(format!(
+                "brightness.sensor.channel ({}) must be 0 to 7",
+                self.sensor.channel
+            ));
+        }
+        problems.extend(
+            self.sensor
+                .spi()
+                .problems("brightness.sensor", MCP3008_MAX_CLOCK_SPEED),
+        );
+        problems
+    }
+}
+
+/// Something that can tell how light the room is
+pub trait AmbientLightSensor {
+    /// From 0.0 in the dark to 1.0 in bright light
+    fn read(&mut self) -> Result<f64>;
+}
+
+/// Reads a light dependent resistor through an MCP3008 ADC over SPI
+pub struct Mcp3008Sensor {
+    spi: Spi,
+    channel: u8,
+}
+
+impl Mcp3008Sensor {
+    pub fn new(config: &SensorConfig) -> Result<Mcp3008Sensor> {
+        Ok(Mcp3008Sensor {
+            spi: config.spi().open()?,
+            channel: config.channel,
+        })
+    }
+}
+
+impl AmbientLightSensor for Mcp3008Sensor {
+    fn read(&mut self) -> Result<f64> {
+        // A start bit, then single ended mode and the channel, then room for the 10 bit answer
+        let request = [1, (8 + self.channel) << 4, 0];
+        let mut response = [0; 3];
+        self.spi.transfer(&mut response, &request)?;
+        let reading = (u16::from(response[1] & 0b11) << 8) | u16::from(response[2]);
+        Ok(f64::from(reading) / 1023.0)
+    }
+}
+
+/// Always reads whatever it was last set to. Clones share the same reading.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct FakeSensor {
+    reading: std::sync::Arc<std::sync::Mutex<f64>>,
+}
+
+#[cfg(test)]
+impl FakeSensor {
+    pub fn set(&self, reading: f64) {
+        *self.reading.lock().unwrap() = reading;
+    }
+}
+
+#[cfg(test)]
+impl AmbientLightSensor for FakeSensor {
+    fn read(&mut self) -> Result<f64> {
+        Ok(*self.reading.lock().unwrap())
+    }
+}
+
+/// Works out how bright the ring should be from the schedule and, if there is one, the
+/// ambient light sensor
+pub struct Dimmer {
+    config: BrightnessConfig,
+    sensor: Option<Box<dyn AmbientLightSensor>>,
+    /// The smoothed ambient level and when the sensor was last read successfully
+    ambient: Option<(f64, Instant)>,
+    last_read: Option<Instant>,
+    failing: bool,
+}
+
+impl Dimmer {
+    pub fn new(config: BrightnessConfig, sensor: Option<Box<dyn AmbientLightSensor>>) -> Dimmer {
+        Dimmer {
+            config,
+            sensor,
+            ambient: None,
+            last_read: None,
+            failing: false,
+        }
+    }
+
+    pub fn from_config(config: &BrightnessConfig) -> Result<Dimmer> {
+        let sensor: Option<Box<dyn AmbientLightSensor>> = if config.sensor.enabled {
+            Some(Box::new(Mcp3008Sensor::new(&config.sensor)?))
+        } else {
+            None
+        };
+        Ok(Dimmer::new(config.clone(), sensor))
+    }
+
+    /// New levels and times, the sensor stays as it is
+    pub fn set_config(&mut self, config: BrightnessConfig) {
+        self.config = config;
+    }
+
+    /// The brightness at `now`, reading the sensor if there is one and it is due
+    pub fn level(&mut self, now: NaiveTime) -> f64 {
+        self.level_at(now, Instant::now())
+    }
+
+    fn level_at(&mut self, now: NaiveTime, instant: Instant) -> f64 {
+        let scheduled = self.config.scheduled_level(now);
+        let sensor = match &mut self.sensor {
+            Some(sensor) => sensor,
+            None => return scheduled,
+        };
+        let due = self
+            .last_read
+            .is_none_or(|last| instant.duration_since(last) >= SENSOR_INTERVAL);
+        if due {
+            self.last_read = Some(instant);
+            match sensor.read() {
+                Ok(reading) => {
+                    if self.failing {
+                        eprintln!("The light sensor is working again");
+                        self.failing = false;
+                    }
+                    let reading = reading.clamp(0.0, 1.0);
+                    let ambient = match self.ambient {
+                        Some((last, read_at)) => {
+                            let carried = AMBIENT_SMOOTHING
+                                .powf(instant.duration_since(read_at).as_secs_f64());
+                            carried * last + (1.0 - carried) * reading
+                        }
+                        None => reading,
+                    };
+                    self.ambient = Some((ambient, instant));
+                }
+                // A bad reading keeps the last good one
+                Err(e) => {
+                    if !self.failing {
+                        eprintln!("Could not read the light sensor: {}", e);
+                        self.failing = true;
+                    }
+                }
+            }
+        }
+        let dark_level = self.config.sensor.dark_level;
+        match self.ambient {
+            Some((ambient, _)) => scheduled * (dark_level + (1.0 - dark_level) * ambient),
+            None => scheduled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn the_night_can_run_past_midnight() {
+        let config = BrightnessConfig::default();
+        assert_eq!(config.scheduled_level(at(21, 59)), 1.0);
+        assert_eq!(config.scheduled_level(at(22, 0)), 0.3);
+        assert_eq!(config.scheduled_level(at(3, 0)), 0.3);
+        assert_eq!(config.scheduled_level(at(7, 0)), 1.0);
+
+        let afternoon_nap = BrightnessConfig {
+            night_start: TimeOfDay(at(13, 0)),
+            night_end: TimeOfDay(at(14, 0)),
+            ..Default::default()
+        };
+        assert!(afternoon_nap.is_night(at(13, 30)));
+        assert!(!afternoon_nap.is_night(at(23, 0)));
+    }
+
+    #[test]
+    fn the_sensor_dims_a_dark_room_gradually() {
+        let sensor = FakeSensor::default();
+        let mut dimmer = Dimmer::new(BrightnessConfig::default(), Some(Box::new(sensor.clone())));
+        let start = Instant::now();
+        let after = |seconds: f64| start + Duration::from_secs_f64(seconds);
+        sensor.set(1.0);
+        assert_eq!(dimmer.level_at(at(12, 0), after(0.0)), 1.0);
+
+        sensor.set(0.0);
+        let first = dimmer.level_at(at(12, 0), after(1.0));
+        assert!(first < 0.9 && first > 0.7, "{}", first);
+        for second in 2..30 {
+            dimmer.level_at(at(12, 0), after(f64::from(second)));
+        }
+        assert!((dimmer.level_at(at(12, 0), after(30.0)) - 0.2).abs() < 0.01);
+        // Dark at night, a fraction of the night level
+        assert!((dimmer.level_at(at(23, 0), after(31.0)) - 0.06).abs() < 0.01);
+    }
+
+    #[test]
+    fn the_sensor_is_read_once_a_second() {
+        let sensor = FakeSensor::default();
+        let mut dimmer = Dimmer::new(BrightnessConfig::default(), Some(Box::new(sensor.clone())));
+        let start = Instant::now();
+        sensor.set(1.0);
+        dimmer.level_at(at(12, 0), start);
+
+        sensor.set(0.0);
+        for frame in 1..10 {
+            let instant = start + Duration::from_millis(frame * 100);
+            assert_eq!(dimmer.level_at(at(12, 0), instant), 1.0);
+        }
+        assert!(dimmer.level_at(at(12, 0), start + SENSOR_INTERVAL) < 1.0);
+    }
+
+    #[test]
+    fn smoothing_follows_the_time_between_readings() {
+        let sensor = FakeSensor::default();
+        let mut dimmer = Dimmer::new(BrightnessConfig::default(), Some(Box::new(sensor.clone())));
+        let start = Instant::now();
+        sensor.set(1.0);
+        dimmer.level_at(at(12, 0), start);
+
+        // After a long gap the old reading has faded away
+        sensor.set(0.0);
+        let level = dimmer.level_at(at(12, 0), start + Duration::from_secs(60));
+        assert!((level - 0.2).abs() < 0.01, "{}", level);
+    }
+}
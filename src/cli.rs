@@ -0,0 +1,79 @@
+use crate::led::test_pattern::TestPattern;
+use crate::pollen::PollenRegion;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Command line flags. Anything set here, or through its environment variable, takes
+/// precedence over the config file.
+#[derive(Debug, Default, StructOpt)]
+#[structopt(
+    name = "flower",
+    about = "A raspberry pi powered flower that shows the current pollen count"
+)]
+pub struct Cli {
+    /// Path to the config file [default: /etc/flower/flower.toml]
+    #[structopt(long, env = "FLOWER_CONFIG", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Draw the LED ring in the terminal instead of on the LEDs
+    #[structopt(long)]
+    pub simulate: bool,
+
+    /// Number of LEDs on the ring
+    #[structopt(long, env = "FLOWER_LED_COUNT")]
+    pub led_count: Option<usize>,
+
+    /// Index of the LED at 12 o'clock
+    #[structopt(long, env = "FLOWER_LED_OFFSET")]
+    pub led_offset: Option<usize>,
+
+    /// GPIO (BCM) pin the motion sensor is connected to
+    #[structopt(long, env = "FLOWER_PIR_PIN")]
+    pub pir_pin: Option<u8>,
+
+    /// Met Office pollen region to display, eg se, nw or scotland
+    #[structopt(long, env = "FLOWER_POLLEN_REGION")]
+    pub pollen_region: Option<PollenRegion>,
+
+    /// Postcode used to pick the pollen region if no region is given here, it replaces the
+    /// region in the config file
+    #[structopt(long, env = "FLOWER_POSTCODE")]
+    pub postcode: Option<String>,
+
+    /// Key for reporting errors through IFTTT
+    #[structopt(long, env = "IFTTT_KEY", hide_env_values = true)]
+    pub ifttt_key: Option<String>,
+
+    /// Key for the Met Office DataPoint weather forecast
+    #[structopt(long, env = "MET_API_KEY", hide_env_values = true)]
+    pub met_api_key: Option<String>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Show LED test patterns to look for dead pixels, wrong colours and the value of
+    /// leds.offset
+    Selftest(SelfTestOptions),
+    /// List the DataPoint sites with forecasts, to find the weather.site_id for somewhere near
+    WeatherSites(WeatherSitesOptions),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SelfTestOptions {
+    /// Only show these patterns, in this order [default: all of them]
+    #[structopt(long = "pattern", possible_values = &TestPattern::NAMES)]
+    pub patterns: Vec<TestPattern>,
+
+    /// How long to show each step for, instead of each pattern's own timing
+    #[structopt(long)]
+    pub step_ms: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct WeatherSitesOptions {
+    /// Only list sites with this in their name or area
+    pub search: Option<String>,
+}
@@ -1,21 +1,122 @@
-use chrono::{Local, Timelike};
+use crate::error::{FlowerError, Result};
+use chrono::{Local, NaiveDateTime, NaiveTime};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-pub struct Clock;
+/// Where the current time comes from
+pub trait TimeSource {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The system clock in the local time zone
+pub struct LocalTimeSource;
+
+impl TimeSource for LocalTimeSource {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Something that can wait, so waiting can be faked in tests. Errors if the wait was cut short.
+pub trait Sleeper {
+    fn sleep(&self, duration: Duration) -> Result<()>;
+}
+
+/// A time source that only moves when told to. Clones share the same time.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakeTimeSource {
+    now: Arc<Mutex<NaiveDateTime>>,
+}
+
+#[cfg(test)]
+impl FakeTimeSource {
+    pub fn new(now: NaiveDateTime) -> FakeTimeSource {
+        FakeTimeSource {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: NaiveDateTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+}
+
+#[cfg(test)]
+impl TimeSource for FakeTimeSource {
+    fn now(&self) -> NaiveDateTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Sleeping moves the fake time on rather than waiting
+#[cfg(test)]
+impl Sleeper for FakeTimeSource {
+    fn sleep(&self, duration: Duration) -> Result<()> {
+        self.advance(chrono::Duration::from_std(duration)?);
+        Ok(())
+    }
+}
+
+pub struct Clock {
+    source: Box<dyn TimeSource>,
+}
 
 impl Clock {
-    pub fn new() -> Clock {
-        Clock
+    pub fn new(source: Box<dyn TimeSource>) -> Clock {
+        Clock { source }
     }
 
-    pub fn get_seconds(&self) -> usize {
-        Local::now().second() as usize
+    pub fn local() -> Clock {
+        Clock::new(Box::new(LocalTimeSource))
     }
 
-    pub fn get_minutes(&self) -> usize {
-        Local::now().minute() as usize
+    /// A single snapshot of the time, read everything for one update from the same snapshot
+    pub fn now(&self) -> NaiveDateTime {
+        self.source.now()
     }
+}
+
+/// A time of day written as "22:30" in the config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = FlowerError;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|_| FlowerError::ConfigError(format!("{} is not a time like 22:30", value)))
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn get_hours(&self) -> usize {
-        Local::now().hour() as usize
+    #[test]
+    fn times_are_read_from_the_config() {
+        assert_eq!(
+            TimeOfDay::try_from("06:45".to_string()).unwrap(),
+            TimeOfDay(NaiveTime::from_hms(6, 45, 0))
+        );
+        assert!(TimeOfDay::try_from("quarter to seven".to_string()).is_err());
     }
 }
@@ -0,0 +1,665 @@
+use crate::brightness::BrightnessConfig;
+use crate::cli::Cli;
+use crate::display::DisplayConfig;
+use crate::error::{FlowerError, Result};
+use crate::led::{Chipset, ColourCorrection, ColourOrder, FillPolicy, WhiteBalance};
+use crate::pollen::{PollenCount, PollenRegion};
+use crate::retry::RetryPolicy;
+use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/flower/flower.toml";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ifttt_key: Option<String>,
+    pub leds: LedConfig,
+    pub spi: SpiConfig,
+    pub pir: PirConfig,
+    pub brightness: BrightnessConfig,
+    pub display: DisplayConfig,
+    pub pollen: PollenConfig,
+    pub weather: WeatherConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LedConfig {
+    pub count: usize,
+    pub offset: usize,
+    pub frame_interval_ms: u64,
+    pub chipset: Chipset,
+    pub colour_order: ColourOrder,
+    pub fill: FillPolicy,
+    pub gamma: bool,
+    pub white_balance: WhiteBalance,
+}
+
+impl Default for LedConfig {
+    fn default() -> Self {
+        LedConfig {
+            count: 24,
+            offset: 12,
+            frame_interval_ms: 100,
+            chipset: Chipset::default(),
+            colour_order: ColourOrder::default(),
+            fill: FillPolicy::default(),
+            gamma: true,
+            white_balance: WhiteBalance::default(),
+        }
+    }
+}
+
+impl LedConfig {
+    pub fn colour_correction(&self) -> ColourCorrection {
+        ColourCorrection {
+            gamma: self.gamma,
+            white_balance: self.white_balance,
+        }
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_millis(self.frame_interval_ms)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpiConfig {
+    pub bus: u8,
+    pub slave_select: u8,
+    pub clock_speed: u32,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        SpiConfig {
+            bus: 0,
+            slave_select: 1,
+            clock_speed: 30_000_000,
+        }
+    }
+}
+
+impl SpiConfig {
+    /// Problems with the settings, for config validation, with `name` before each field
+    pub fn problems(&self, name: &str, max_clock_speed: u32) -> Vec<String> {
+        let mut problems = vec![];
+        if self.bus > 2 {
+            problems.push(format!("{}.bus ({}) must be 0, 1 or 2", name, self.bus));
+        }
+        if self.slave_select > 2 {
+            problems.push(format!(
+                "{}.slave_select ({}) must be 0, 1 or 2",
+                name, self.slave_select
+            ));
+        }
+        if self.clock_speed == 0 || self.clock_speed > max_clock_speed {
+            problems.push(format!(
+                "{}.clock_speed ({}) must be between 1 and {} Hz",
+                name, self.clock_speed, max_clock_speed
+            ));
+        }
+        problems
+    }
+
+    /// Opens the bus, for the LEDs or anything else on SPI
+    pub fn open(&self) -> Result<Spi> {
+        let bus = match self.bus {
+            0 => Bus::Spi0,
+            1 => Bus::Spi1,
+            2 => Bus::Spi2,
+            x => return Err(FlowerError::ConfigError(format!("no SPI bus {}", x)).into()),
+        };
+        let slave_select = match self.slave_select {
+            0 => SlaveSelect::Ss0,
+            1 => SlaveSelect::Ss1,
+            2 => SlaveSelect::Ss2,
+            x => return Err(FlowerError::ConfigError(format!("no SPI slave select {}", x)).into()),
+        };
+        Ok(Spi::new(bus, slave_select, self.clock_speed, Mode::Mode0)?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PirConfig {
+    pub enabled: bool,
+    pub pin: u8,
+    pub timeout_secs: u64,
+}
+
+impl Default for PirConfig {
+    fn default() -> Self {
+        PirConfig {
+            enabled: true,
+            pin: 17,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl PirConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// Where to get the pollen forecast from, each is tried in turn until one answers
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ProviderConfig {
+    /// Scrape the Met Office pollen page for the configured region
+    MetOffice,
+    /// Fetch a JSON forecast from a URL
+    Json { url: String },
+    /// Read a JSON forecast from a local file
+    File { path: PathBuf },
+    /// Always show the same count
+    Fixed { count: PollenCount },
+}
+
+/// The pollen region can be given directly, or looked up from a postcode or a coordinate. If
+/// none of them are set the south east is used.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollenConfig {
+    pub region: Option<PollenRegion>,
+    pub postcode: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub refresh_mins: u64,
+    pub show_tomorrow: bool,
+    pub tomorrow_leds: usize,
+    pub providers: Vec<ProviderConfig>,
+    pub cache: bool,
+    pub cache_path: PathBuf,
+    pub stale_after_hours: u64,
+    pub retry: RetryPolicy,
+}
+
+impl Default for PollenConfig {
+    fn default() -> Self {
+        PollenConfig {
+            region: None,
+            postcode: None,
+            latitude: None,
+            longitude: None,
+            refresh_mins: 60,
+            show_tomorrow: false,
+            tomorrow_leds: 3,
+            providers: vec![ProviderConfig::MetOffice],
+            cache: true,
+            cache_path: PathBuf::from("/var/lib/flower/pollen.json"),
+            stale_after_hours: 6,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl PollenConfig {
+    /// Where to keep the last forecast, None if it shouldn't be kept
+    pub fn cache_path(&self) -> Option<PathBuf> {
+        if self.cache {
+            Some(self.cache_path.clone())
+        } else {
+            None
+        }
+    }
+
+    /// How old a forecast can be before it is shown dimmed
+    pub fn stale_after(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.stale_after_hours as i64)
+    }
+
+    /// How many LEDs show tomorrow's pollen count, 0 if it isn't shown
+    pub fn tomorrow_leds(&self) -> usize {
+        if self.show_tomorrow {
+            self.tomorrow_leds
+        } else {
+            0
+        }
+    }
+
+    /// Picks the region one way only, forgetting any other way it was set
+    fn set_location(&mut self, region: Option<PollenRegion>, postcode: Option<String>) {
+        self.region = region;
+        self.postcode = postcode;
+        self.latitude = None;
+        self.longitude = None;
+    }
+
+    pub fn region(&self) -> std::result::Result<PollenRegion, FlowerError> {
+        if let Some(region) = self.region {
+            return Ok(region);
+        }
+        if let Some(postcode) = &self.postcode {
+            return PollenRegion::from_postcode(postcode);
+        }
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => {
+                PollenRegion::from_coordinates(latitude, longitude)
+            }
+            (None, None) => Ok(PollenRegion::default()),
+            _ => Err(FlowerError::ConfigError(
+                "pollen.latitude and pollen.longitude must be set together".to_string(),
+            )),
+        }
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.refresh_mins * 60)
+    }
+}
+
+/// What the weather mode colours the ring by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WeatherMeasure {
+    #[default]
+    Temperature,
+    /// The chance of rain, snow or hail
+    Precipitation,
+}
+
+/// The Met Office DataPoint forecast for the weather mode. Nothing is fetched unless both the
+/// key and the site are set.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub api_key: Option<String>,
+    /// The DataPoint site to show the forecast for, see `flower weather-sites`
+    pub site_id: Option<u32>,
+    pub show: WeatherMeasure,
+    /// Temperatures at or below this are blue
+    pub cold_c: f64,
+    /// Temperatures at or above this are red
+    pub hot_c: f64,
+    pub refresh_mins: u64,
+    pub retry: RetryPolicy,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            api_key: None,
+            site_id: None,
+            show: WeatherMeasure::default(),
+            cold_c: 0.0,
+            hot_c: 25.0,
+            refresh_mins: 60,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl WeatherConfig {
+    /// The key and site to fetch the forecast with, None if either isn't set
+    pub fn site(&self) -> Option<(String, u32)> {
+        Some((self.api_key.clone()?, self.site_id?))
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        Duration::from_secs(self.refresh_mins * 60)
+    }
+}
+
+impl Config {
+    /// Reads the config file, applies anything set on the command line or in the environment
+    /// and checks the result makes sense.
+    ///
+    /// A missing file is fine if it's the default path, the defaults are used instead.
+    pub fn load(cli: &Cli) -> Result<Config> {
+        let path = cli
+            .config
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+        let mut config = if path.exists() || cli.config.is_some() {
+            Config::from_file(&path)?
+        } else {
+            Config::default()
+        };
+        config.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path).map_err(|e| {
+            FlowerError::ConfigError(format!("could not read {}: {}", path.display(), e))
+        })?;
+        Config::from_toml(&text)
+            .map_err(|e| FlowerError::ConfigError(format!("{}: {}", path.display(), e)).into())
+    }
+
+    pub fn from_toml(text: &str) -> std::result::Result<Config, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(count) = cli.led_count {
+            self.leds.count = count;
+        }
+        if let Some(offset) = cli.led_offset {
+            self.leds.offset = offset;
+        }
+        if let Some(pin) = cli.pir_pin {
+            self.pir.pin = pin;
+        }
+        // A region or postcode given here replaces however the file picks the region
+        if let Some(region) = cli.pollen_region {
+            self.pollen.set_location(Some(region), None);
+        } else if let Some(postcode) = &cli.postcode {
+            self.pollen.set_location(None, Some(postcode.clone()));
+        }
+        if let Some(key) = &cli.ifttt_key {
+            self.ifttt_key = Some(key.clone());
+        }
+        if let Some(key) = &cli.met_api_key {
+            self.weather.api_key = Some(key.clone());
+        }
+    }
+
+    /// Checks every value, reporting all of the problems at once rather than just the first
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+        if self.leds.count == 0 {
+            problems.push("leds.count must be at least 1".to_string());
+        }
+        if self.leds.offset >= self.leds.count.max(1) {
+            problems.push(format!(
+                "leds.offset ({}) must be less than leds.count ({})",
+                self.leds.offset, self.leds.count
+            ));
+        }
+        if self.leds.frame_interval_ms == 0 {
+            problems.push("leds.frame_interval_ms must be at least 1".to_string());
+        }
+        let balance = &self.leds.white_balance;
+        for (name, value) in [
+            ("red", balance.red),
+            ("green", balance.green),
+            ("blue", balance.blue),
+        ]
+        .iter()
+        {
+            if !(0.0..=1.0).contains(value) {
+                problems.push(format!(
+                    "leds.white_balance.{} must be between 0.0 and 1.0",
+                    name
+                ));
+            }
+        }
+        problems.extend(self.spi.problems("spi", 125_000_000));
+        if self.pir.pin > 27 {
+            problems.push(format!(
+                "pir.pin ({}) must be a BCM GPIO number from 0 to 27",
+                self.pir.pin
+            ));
+        }
+        if self.pir.timeout_secs == 0 {
+            problems.push("pir.timeout_secs must be at least 1".to_string());
+        }
+        if let Err(FlowerError::ConfigError(problem)) = self.pollen.region() {
+            problems.push(problem);
+        }
+        if self.pollen.refresh_mins == 0 {
+            problems.push("pollen.refresh_mins must be at least 1".to_string());
+        }
+        if self.pollen.stale_after_hours == 0 {
+            problems.push("pollen.stale_after_hours must be at least 1".to_string());
+        }
+        problems.extend(self.brightness.problems());
+        let sensor = self.brightness.sensor.spi();
+        if self.brightness.sensor.enabled
+            && (sensor.bus, sensor.slave_select) == (self.spi.bus, self.spi.slave_select)
+        {
+            problems.push(format!(
+                "brightness.sensor can't use SPI bus {} slave select {}, the LEDs are on it",
+                sensor.bus, sensor.slave_select
+            ));
+        }
+        problems.extend(self.display.problems());
+        problems.extend(self.pollen.retry.problems("pollen.retry"));
+        if self.weather.site_id.is_some() && self.weather.api_key.is_none() {
+            problems.push(
+                "weather.api_key must be set to fetch the forecast for weather.site_id".to_string(),
+            );
+        }
+        if self.weather.cold_c >= self.weather.hot_c {
+            problems.push(format!(
+                "weather.cold_c ({}) must be less than weather.hot_c ({})",
+                self.weather.cold_c, self.weather.hot_c
+            ));
+        }
+        if self.weather.refresh_mins == 0 {
+            problems.push("weather.refresh_mins must be at least 1".to_string());
+        }
+        problems.extend(self.weather.retry.problems("weather.retry"));
+        if self.pollen.providers.is_empty() {
+            problems.push("pollen.providers must have at least one provider".to_string());
+        }
+        if self.pollen.show_tomorrow
+            && (self.pollen.tomorrow_leds == 0 || self.pollen.tomorrow_leds >= self.leds.count)
+        {
+            problems.push(format!(
+                "pollen.tomorrow_leds ({}) must be at least 1 and less than leds.count ({})",
+                self.pollen.tomorrow_leds, self.leds.count
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(FlowerError::ConfigError(problems.join(", ")).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_file_gives_the_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.leds.count, 24);
+        assert_eq!(config.leds.offset, 12);
+        assert_eq!(config.pir.pin, 17);
+        assert_eq!(config.pollen.region().unwrap(), PollenRegion::SouthEast);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn the_default_colour_correction_shows_medium_pollen_as_yellow() {
+        let correction = LedConfig::default().colour_correction();
+        let yellow = correction.apply(&Some(PollenCount::Medium).into());
+        assert_eq!((yellow.red(), yellow.blue()), (255, 0));
+        // Orange has about half as much green as red
+        assert!(yellow.green() > 170, "{:?} looks orange", yellow);
+    }
+
+    #[test]
+    fn values_in_the_file_replace_the_defaults() {
+        let config = Config::from_toml(
+            r#"
+            ifttt_key = "abc"
+
+            [leds]
+            count = 60
+
+            [pollen]
+            region = "nw"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ifttt_key, Some("abc".to_string()));
+        assert_eq!(config.leds.count, 60);
+        assert_eq!(config.leds.offset, 12);
+        assert_eq!(config.pollen.region().unwrap(), PollenRegion::NorthWest);
+    }
+
+    #[test]
+    fn the_pollen_region_can_come_from_a_postcode() {
+        let config = Config::from_toml("[pollen]\npostcode = \"EH1 1YZ\"").unwrap();
+        assert_eq!(config.pollen.region().unwrap(), PollenRegion::Scotland);
+        assert!(Config::from_toml("[pollen]\nregion = \"narnia\"").is_err());
+    }
+
+    #[test]
+    fn pollen_providers_are_read_in_order() {
+        let config = Config::from_toml(
+            r#"
+            [[pollen.providers]]
+            type = "met_office"
+
+            [[pollen.providers]]
+            type = "file"
+            path = "/home/pi/pollen.json"
+
+            [[pollen.providers]]
+            type = "fixed"
+            count = "medium"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.pollen.providers,
+            vec![
+                ProviderConfig::MetOffice,
+                ProviderConfig::File {
+                    path: PathBuf::from("/home/pi/pollen.json")
+                },
+                ProviderConfig::Fixed {
+                    count: PollenCount::Medium
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn the_weather_needs_a_key_for_its_site() {
+        let config = Config::from_toml(
+            r#"
+            [weather]
+            site_id = 310069
+            show = "precipitation"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.weather.show, WeatherMeasure::Precipitation);
+        assert_eq!(config.weather.site(), None);
+        assert!(config.validate().is_err());
+
+        let mut config = config;
+        config.apply_cli(&Cli {
+            met_api_key: Some("abc".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(config.weather.site(), Some(("abc".to_string(), 310069)));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(Config::from_toml("[leds]\ncuont = 24").is_err());
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_file() {
+        let mut config = Config::from_toml("[leds]\ncount = 60").unwrap();
+        config.apply_cli(&Cli {
+            led_count: Some(12),
+            pir_pin: Some(4),
+            ..Default::default()
+        });
+        assert_eq!(config.leds.count, 12);
+        assert_eq!(config.pir.pin, 4);
+    }
+
+    #[test]
+    fn a_region_from_the_command_line_replaces_the_one_in_the_file() {
+        let file = "[pollen]\nregion = \"se\"\nlatitude = 55.95\nlongitude = -3.19";
+        let mut config = Config::from_toml(file).unwrap();
+        config.apply_cli(&Cli {
+            postcode: Some("EH1 1YZ".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(config.pollen.region().unwrap(), PollenRegion::Scotland);
+        assert_eq!(config.pollen.latitude, None);
+
+        let mut config = Config::from_toml("[pollen]\npostcode = \"EH1 1YZ\"").unwrap();
+        config.apply_cli(&Cli {
+            pollen_region: Some(PollenRegion::Wales),
+            postcode: Some("BN1".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(config.pollen.region().unwrap(), PollenRegion::Wales);
+        assert_eq!(config.pollen.postcode, None);
+    }
+
+    #[test]
+    fn the_example_config_is_valid() {
+        let config = Config::from_toml(include_str!("../flower.toml")).unwrap();
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let config = Config::from_toml(
+            r#"
+            [leds]
+            count = 12
+            offset = 12
+
+            [pir]
+            pin = 40
+
+            [pollen.retry]
+            jitter = 1.5
+            "#,
+        )
+        .unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("leds.offset"));
+        assert!(message.contains("pir.pin"));
+        assert!(message.contains("pollen.retry.jitter"));
+    }
+
+    #[test]
+    fn the_light_sensor_needs_its_own_place_on_the_bus() {
+        let config = Config::from_toml(
+            r#"
+            [spi]
+            bus = 0
+            slave_select = 1
+
+            [brightness.sensor]
+            enabled = true
+            bus = 3
+            slave_select = 1
+            clock_speed = 0
+            "#,
+        )
+        .unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("brightness.sensor.bus"));
+        assert!(message.contains("brightness.sensor.clock_speed"));
+
+        let shared = Config::from_toml(
+            r#"
+            [brightness.sensor]
+            enabled = true
+            bus = 0
+            slave_select = 1
+            "#,
+        )
+        .unwrap();
+        let message = shared.validate().unwrap_err().to_string();
+        assert!(message.contains("the LEDs are on it"), "{}", message);
+    }
+}
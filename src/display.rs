@@ -0,0 +1,625 @@
+mod animation;
+mod clock;
+mod countdown;
+mod gauge;
+mod glow;
+mod weather;
+
+use crate::clock::{Clock, TimeOfDay};
+use crate::config::Config;
+use crate::error::Result;
+use crate::led::value::{LED_LOW_WHITE, LED_RED};
+use crate::led::{
+    BlendMode, ClockFace, ClockTheme, Compositor, LayerKind, LayerStyle, LedArray, LedEffect,
+    LedValue, LedWritable,
+};
+use crate::met_api::SaneForecast;
+use crate::pollen::PollenCount;
+use chrono::{NaiveDateTime, NaiveTime, Timelike};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+pub use animation::{Easing, Fade};
+pub use clock::ClockMode;
+pub use countdown::CountdownMode;
+pub use gauge::PollenGaugeMode;
+pub use glow::GlowMode;
+pub use weather::WeatherMode;
+
+/// How much the pollen colours are dimmed when the forecast is stale
+const STALE_DIM: f64 = 0.35;
+
+/// A slow blink at the top of the ring while the forecast is out of date
+const STALE_ALERT_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 3_000,
+    floor: 0.0,
+};
+
+/// How long a notification takes to go once round the ring
+const NOTIFICATION_MS: i64 = 1_500;
+
+/// How many LEDs a notification trails behind it
+const NOTIFICATION_TAIL: usize = 4;
+
+/// The things the ring can show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeKind {
+    /// The time over today's pollen colour
+    #[default]
+    Clock,
+    /// Just the pollen count, as a gauge filling the ring
+    Pollen,
+    /// The weather for the day ahead
+    Weather,
+    /// The ring empties over `display.countdown_mins` from when the mode is switched to
+    Countdown,
+    /// A slow, dim glow in the pollen colour
+    Glow,
+}
+
+impl ModeKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Clock => "clock",
+            Self::Pollen => "pollen",
+            Self::Weather => "weather",
+            Self::Countdown => "countdown",
+            Self::Glow => "glow",
+        }
+    }
+}
+
+impl fmt::Display for ModeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Switch to `mode` every day at `at`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    pub at: TimeOfDay,
+    pub mode: ModeKind,
+}
+
+/// How each layer of the ring is drawn on the ones below it. The hands all share one style.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayersConfig {
+    pub background: LayerStyle,
+    pub hands: LayerStyle,
+    pub alerts: LayerStyle,
+    pub notifications: LayerStyle,
+}
+
+impl LayersConfig {
+    fn styles(&self) -> [(LayerKind, LayerStyle); 6] {
+        [
+            (LayerKind::Background, self.background),
+            (LayerKind::HourHand, self.hands),
+            (LayerKind::MinuteHand, self.hands),
+            (LayerKind::SecondHand, self.hands),
+            (LayerKind::Alerts, self.alerts),
+            (LayerKind::Notifications, self.notifications),
+        ]
+    }
+}
+
+/// What the ring shows and how it moves between frames
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// The mode at start up, until the schedule says otherwise
+    pub mode: ModeKind,
+    pub smooth_hands: bool,
+    /// How long to cross-fade when switching modes or when the pollen colour changes, 0 to
+    /// switch straight away
+    pub fade_ms: u64,
+    pub easing: Easing,
+    pub countdown_mins: u64,
+    pub schedule: Vec<ScheduleEntry>,
+    pub layers: LayersConfig,
+    pub clock: ClockFace,
+    /// Clock themes by name, as well as the built in ones
+    pub themes: HashMap<String, ClockTheme>,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            mode: ModeKind::default(),
+            smooth_hands: true,
+            fade_ms: 1_000,
+            easing: Easing::default(),
+            countdown_mins: 25,
+            schedule: vec![],
+            layers: LayersConfig::default(),
+            clock: ClockFace::default(),
+            themes: HashMap::new(),
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub fn fade(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds(self.fade_ms as i64)
+    }
+
+    /// The mode the schedule says to show at `now`. The last entry of the day carries on past
+    /// midnight until the first.
+    pub fn scheduled_mode(&self, now: NaiveTime) -> Option<ModeKind> {
+        let latest = |entries: &mut dyn Iterator<Item = &ScheduleEntry>| {
+            entries
+                .max_by_key(|entry| entry.at.0)
+                .map(|entry| entry.mode)
+        };
+        latest(&mut self.schedule.iter().filter(|entry| entry.at.0 <= now))
+            .or_else(|| latest(&mut self.schedule.iter()))
+    }
+
+    /// The clock face's theme, one from the config takes the place of a built in one with the
+    /// same name
+    pub fn theme(&self) -> Option<ClockTheme> {
+        let name = &self.clock.theme;
+        self.themes
+            .get(name)
+            .copied()
+            .or_else(|| ClockTheme::built_in(name))
+    }
+
+    /// Problems with the settings, for config validation
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.countdown_mins == 0 {
+            problems.push("display.countdown_mins must be at least 1".to_string());
+        }
+        if self.theme().is_none() {
+            problems.push(format!(
+                "display.clock.theme ({}) is not a built in theme or in display.themes",
+                self.clock.theme
+            ));
+        }
+        for (name, style) in [
+            ("background", self.layers.background),
+            ("hands", self.layers.hands),
+            ("alerts", self.layers.alerts),
+            ("notifications", self.layers.notifications),
+        ]
+        .iter()
+        {
+            if !(0.0..=1.0).contains(&style.opacity) {
+                problems.push(format!(
+                    "display.layers.{}.opacity must be between 0.0 and 1.0",
+                    name
+                ));
+            }
+        }
+        for (index, entry) in self.schedule.iter().enumerate() {
+            if self.schedule[..index]
+                .iter()
+                .any(|other| other.at == entry.at)
+            {
+                problems.push(format!(
+                    "display.schedule has more than one entry at {}",
+                    entry.at
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// What today's and tomorrow's pollen counts are and whether the forecast is out of date
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PollenShown {
+    pub today: Option<PollenCount>,
+    pub tomorrow: Option<PollenCount>,
+    pub stale: bool,
+}
+
+/// Everything a mode might draw, as of a single moment
+pub struct Scene<'a> {
+    pub now: NaiveDateTime,
+    pub pollen: PollenShown,
+    pub weather: Option<&'a SaneForecast>,
+}
+
+impl Scene<'_> {
+    fn dim(&self, value: LedValue) -> LedValue {
+        if self.pollen.stale {
+            value.scaled(STALE_DIM)
+        } else {
+            value
+        }
+    }
+
+    pub fn pollen_colour(&self) -> LedValue {
+        self.dim(self.pollen.today.into())
+    }
+
+    pub fn pollen_effect(&self) -> LedEffect {
+        self.pollen.today.into()
+    }
+
+    pub fn tomorrow_colour(&self) -> Option<LedValue> {
+        self.pollen
+            .tomorrow
+            .map(|count| self.dim(Some(count).into()))
+    }
+
+    /// Milliseconds since midnight, for effects
+    pub fn elapsed_ms(&self) -> u64 {
+        self.now.num_seconds_from_midnight() as u64 * 1_000
+            + (self.now.nanosecond() / 1_000_000) as u64
+    }
+}
+
+/// One of the things the ring can show
+pub trait DisplayMode {
+    fn kind(&self) -> ModeKind;
+
+    /// Called each time the mode is switched to
+    fn activate(&mut self, _now: NaiveDateTime) {}
+
+    /// Takes the settings from a reloaded config, carrying on with anything under way
+    fn reconfigure(&mut self, _config: &Config) {}
+
+    /// Draws `scene` onto the background and hand layers, which start each frame empty
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()>;
+}
+
+/// Every mode, which one is showing, and the fade between them
+pub struct Display {
+    clock: Clock,
+    modes: Vec<Box<dyn DisplayMode>>,
+    active: usize,
+    config: DisplayConfig,
+    /// The last mode the schedule switched to, so a mode picked by hand lasts until the
+    /// schedule next changes
+    scheduled: Option<ModeKind>,
+    fade: Option<Fade>,
+    last_pollen: Option<PollenShown>,
+    notified_at: Option<NaiveDateTime>,
+    layers: Compositor,
+    /// What the layers made
+    drawn: LedArray,
+    /// What is being shown, part way through a fade
+    shown: Vec<LedValue>,
+}
+
+impl Display {
+    pub fn new(config: &Config, clock: Clock) -> Display {
+        let modes: Vec<Box<dyn DisplayMode>> = vec![
+            Box::new(ClockMode::new(config)),
+            Box::new(PollenGaugeMode),
+            Box::new(WeatherMode::new(&config.weather)),
+            Box::new(CountdownMode::new(chrono::Duration::minutes(
+                config.display.countdown_mins as i64,
+            ))),
+            Box::new(GlowMode),
+        ];
+        let mut display = Display {
+            clock,
+            modes,
+            active: 0,
+            config: config.display.clone(),
+            scheduled: None,
+            fade: None,
+            last_pollen: None,
+            notified_at: None,
+            layers: Display::layers(config),
+            drawn: LedArray::new(config.leds.count, config.leds.offset),
+            shown: vec![LedValue::default(); config.leds.count],
+        };
+        let now = display.clock.now();
+        display.switch_at(config.display.mode, now);
+        display.fade = None;
+        display
+    }
+
+    fn layers(config: &Config) -> Compositor {
+        let mut layers = Compositor::new(config.leds.count, config.leds.offset);
+        for (kind, style) in config.display.layers.styles().iter() {
+            layers.set_style(*kind, *style);
+        }
+        layers
+    }
+
+    /// Takes the settings from a reloaded config. The mode showing, even one picked by hand,
+    /// and a running countdown carry on. The number of LEDs must be the same.
+    pub fn reconfigure(&mut self, config: &Config) {
+        for mode in self.modes.iter_mut() {
+            mode.reconfigure(config);
+        }
+        self.config = config.display.clone();
+        self.layers = Display::layers(config);
+        self.drawn = LedArray::new(config.leds.count, config.leds.offset);
+    }
+
+    pub fn active(&self) -> ModeKind {
+        self.modes[self.active].kind()
+    }
+
+    fn switch_at(&mut self, kind: ModeKind, now: NaiveDateTime) {
+        if let Some(index) = self.modes.iter().position(|mode| mode.kind() == kind) {
+            self.active = index;
+            self.modes[index].activate(now);
+            self.fade = Some(Fade::new(
+                self.shown.clone(),
+                now,
+                self.config.fade(),
+                self.config.easing,
+            ));
+        }
+    }
+
+    /// Fades to the next mode, back to the first after the last
+    pub fn next_mode(&mut self) {
+        let next = self.modes[(self.active + 1) % self.modes.len()].kind();
+        let now = self.clock.now();
+        self.switch_at(next, now);
+    }
+
+    /// Sends a light once round the ring, to acknowledge something like a reloaded config
+    pub fn notify(&mut self) {
+        self.notified_at = Some(self.clock.now());
+    }
+
+    fn draw_alerts(&mut self, scene: &Scene) {
+        if scene.pollen.stale {
+            let alert = STALE_ALERT_EFFECT.apply(LED_RED, scene.elapsed_ms());
+            self.layers.layer(LayerKind::Alerts).set(0, alert);
+        }
+    }
+
+    fn draw_notifications(&mut self, now: NaiveDateTime) {
+        let notified_at = match self.notified_at {
+            Some(notified_at) => notified_at,
+            None => return,
+        };
+        let progress = (now - notified_at).num_milliseconds() as f64 / NOTIFICATION_MS as f64;
+        if progress >= 1.0 {
+            self.notified_at = None;
+            return;
+        }
+        let layer = self.layers.layer(LayerKind::Notifications);
+        let position = layer.position(progress, 1.0);
+        layer.hand(position, LED_LOW_WHITE, NOTIFICATION_TAIL, BlendMode::Over);
+    }
+
+    /// Asks the active mode to draw the scene at the current time
+    pub fn render(
+        &mut self,
+        pollen: PollenShown,
+        weather: Option<&SaneForecast>,
+    ) -> Result<&mut Self> {
+        let now = self.clock.now();
+        if let Some(mode) = self.config.scheduled_mode(now.time()) {
+            if self.scheduled != Some(mode) {
+                self.scheduled = Some(mode);
+                if mode != self.active() {
+                    self.switch_at(mode, now);
+                }
+            }
+        }
+        if self.last_pollen.is_some_and(|last| last != pollen) {
+            self.fade = Some(Fade::new(
+                self.shown.clone(),
+                now,
+                self.config.fade(),
+                self.config.easing,
+            ));
+        }
+        self.last_pollen = Some(pollen);
+
+        let scene = Scene {
+            now,
+            pollen,
+            weather,
+        };
+        self.layers.clear();
+        self.modes[self.active].draw(&scene, &mut self.layers)?;
+        self.draw_alerts(&scene);
+        self.draw_notifications(now);
+        self.layers.composite(&mut self.drawn);
+        match &self.fade {
+            Some(fade) if !fade.is_finished(now) => {
+                fade.apply(self.drawn.as_array(), now, &mut self.shown)
+            }
+            _ => {
+                self.fade = None;
+                self.shown.copy_from_slice(self.drawn.as_array());
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl LedWritable for Display {
+    fn as_array(&self) -> &[LedValue] {
+        &self.shown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeTimeSource;
+    use crate::led::value::LED_LOW_GREEN;
+    use chrono::{Duration, NaiveDate};
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2020, 7, 1).and_hms(hour, minute, 0)
+    }
+
+    fn display_at(config: &Config, now: NaiveDateTime) -> (Display, FakeTimeSource) {
+        let time = FakeTimeSource::new(now);
+        let display = Display::new(config, Clock::new(Box::new(time.clone())));
+        (display, time)
+    }
+
+    #[test]
+    fn modes_are_named_in_the_config() {
+        let config = Config::from_toml(
+            r#"
+            [display]
+            mode = "glow"
+            schedule = [{ at = "07:00", mode = "clock" }, { at = "22:30", mode = "glow" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.display.mode, ModeKind::Glow);
+        assert_eq!(config.display.schedule.len(), 2);
+    }
+
+    #[test]
+    fn clock_themes_come_from_the_config() {
+        let config = Config::from_toml(
+            r##"
+            [display.clock]
+            theme = "night"
+            quarter_markers = true
+
+            [display.themes.night]
+            hour = "#400000"
+            minute = "hsl(0, 100%, 25%)"
+            "##,
+        )
+        .unwrap();
+        let theme = config.display.theme().unwrap();
+        assert_eq!(theme.hour.0, LedValue::new(1, 64, 0, 0).unwrap());
+        assert_eq!(theme.minute.0, LedValue::new(1, 128, 0, 0).unwrap());
+        // Anything left out is from the classic theme
+        assert_eq!(theme.second, ClockTheme::default().second);
+        assert!(config.display.clock.quarter_markers);
+
+        let unknown = Config::from_toml("[display.clock]\ntheme = \"disco\"").unwrap();
+        assert!(unknown.validate().is_err());
+        assert!(Config::from_toml("[display.themes.bad]\nhour = \"red\"").is_err());
+    }
+
+    #[test]
+    fn the_schedule_runs_past_midnight() {
+        let mut config = DisplayConfig::default();
+        assert_eq!(config.scheduled_mode(at(3, 0).time()), None);
+        config.schedule = vec![
+            ScheduleEntry {
+                at: TimeOfDay(at(22, 0).time()),
+                mode: ModeKind::Glow,
+            },
+            ScheduleEntry {
+                at: TimeOfDay(at(7, 0).time()),
+                mode: ModeKind::Clock,
+            },
+        ];
+        assert_eq!(config.scheduled_mode(at(3, 0).time()), Some(ModeKind::Glow));
+        assert_eq!(
+            config.scheduled_mode(at(7, 0).time()),
+            Some(ModeKind::Clock)
+        );
+        assert_eq!(
+            config.scheduled_mode(at(23, 0).time()),
+            Some(ModeKind::Glow)
+        );
+    }
+
+    #[test]
+    fn modes_switch_by_hand_and_by_schedule() {
+        let mut config = Config::default();
+        config.display.schedule = vec![ScheduleEntry {
+            at: TimeOfDay(at(12, 0).time()),
+            mode: ModeKind::Pollen,
+        }];
+        let (mut display, time) = display_at(&config, at(11, 0));
+        display.render(PollenShown::default(), None).unwrap();
+        assert_eq!(display.active(), ModeKind::Pollen);
+
+        // Picking a mode by hand lasts until the schedule next changes
+        display.next_mode();
+        assert_eq!(display.active(), ModeKind::Weather);
+        time.set(at(12, 30));
+        display.render(PollenShown::default(), None).unwrap();
+        assert_eq!(display.active(), ModeKind::Weather);
+        for _ in 0..3 {
+            display.next_mode();
+        }
+        assert_eq!(display.active(), ModeKind::Clock);
+    }
+
+    #[test]
+    fn reloading_keeps_the_mode_and_a_running_countdown() {
+        let mut config = Config::default();
+        config.display.fade_ms = 0;
+        let (mut display, time) = display_at(&config, at(12, 0));
+        for _ in 0..3 {
+            display.next_mode();
+        }
+        assert_eq!(display.active(), ModeKind::Countdown);
+
+        time.advance(Duration::seconds(270));
+        config.display.countdown_mins = 30;
+        config.display.smooth_hands = false;
+        display.reconfigure(&config);
+        display.render(PollenShown::default(), None).unwrap();
+        assert_eq!(display.active(), ModeKind::Countdown);
+        // 25.5 of the 30 minutes are left, so the last few LEDs have gone out
+        assert_eq!(display.drawn.get(19), LED_LOW_GREEN);
+        assert_eq!(display.drawn.get(22), LedValue::default());
+    }
+
+    #[test]
+    fn switching_modes_fades_over_time() {
+        let config = Config::default();
+        let (mut display, time) = display_at(&config, at(12, 0));
+        let pollen = PollenShown {
+            today: Some(PollenCount::High),
+            ..Default::default()
+        };
+        display.render(pollen, None).unwrap();
+        let clock_face = display.as_array().to_vec();
+
+        display.next_mode();
+        display.render(pollen, None).unwrap();
+        assert_eq!(display.as_array(), clock_face.as_slice());
+
+        time.advance(Duration::milliseconds(500));
+        display.render(pollen, None).unwrap();
+        let half_way = display.as_array().to_vec();
+        assert_ne!(half_way, clock_face);
+
+        // A single late frame finishes the fade
+        time.advance(Duration::seconds(5));
+        display.render(pollen, None).unwrap();
+        let gauge = display.as_array().to_vec();
+        assert_ne!(gauge, half_way);
+        display.render(pollen, None).unwrap();
+        assert_eq!(display.as_array(), gauge.as_slice());
+    }
+
+    #[test]
+    fn alerts_and_notifications_are_drawn_over_the_mode() {
+        let mut config = Config::default();
+        config.display.mode = ModeKind::Glow;
+        config.display.fade_ms = 0;
+        let (mut display, time) = display_at(&config, at(12, 0));
+        let stale = PollenShown {
+            stale: true,
+            ..Default::default()
+        };
+        display.render(stale, None).unwrap();
+        assert_eq!(display.drawn.get(0), LED_RED);
+        assert_ne!(display.drawn.get(1), LED_RED);
+
+        // A notification goes over the alert, then leaves it showing again
+        display.notify();
+        display.render(stale, None).unwrap();
+        assert_eq!(display.drawn.get(0), LED_LOW_WHITE);
+        // A whole pulse later, so the alert is back at full brightness
+        time.advance(Duration::seconds(3));
+        display.render(stale, None).unwrap();
+        assert_eq!(display.drawn.get(0), LED_RED);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::led::LedValue;
+use chrono::{Duration, NaiveDateTime};
+use serde::Deserialize;
+
+/// How an animation speeds up and slows down, turning how far through it is in time into how
+/// far through it is in colour
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    /// Starts slowly
+    EaseIn,
+    /// Finishes slowly
+    EaseOut,
+    /// Starts and finishes slowly
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// From `t` between 0.0 and 1.0 to a value between 0.0 and 1.0
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A cross-fade from one frame to whatever is drawn next. How far it has got comes from the
+/// time, not from how many frames were shown, so a late frame just jumps further ahead.
+pub struct Fade {
+    from: Vec<LedValue>,
+    started: NaiveDateTime,
+    duration: Duration,
+    easing: Easing,
+}
+
+impl Fade {
+    pub fn new(
+        from: Vec<LedValue>,
+        started: NaiveDateTime,
+        duration: Duration,
+        easing: Easing,
+    ) -> Fade {
+        Fade {
+            from,
+            started,
+            duration,
+            easing,
+        }
+    }
+
+    /// How far from the old frame to the new one at `now`, from 0.0 to 1.0
+    pub fn progress(&self, now: NaiveDateTime) -> f64 {
+        if self.duration <= Duration::zero() {
+            return 1.0;
+        }
+        let elapsed = (now - self.started).num_milliseconds() as f64;
+        self.easing
+            .apply(elapsed / self.duration.num_milliseconds() as f64)
+    }
+
+    pub fn is_finished(&self, now: NaiveDateTime) -> bool {
+        now - self.started >= self.duration
+    }
+
+    /// Mixes the old frame with `to` into `into`
+    pub fn apply(&self, to: &[LedValue], now: NaiveDateTime, into: &mut [LedValue]) {
+        let progress = self.progress(now);
+        for ((led, from), to) in into.iter_mut().zip(&self.from).zip(to) {
+            *led = from.blend(to, progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::value::{LED_LOW_BLUE, LED_LOW_RED, LED_RED};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn every_easing_starts_at_0_and_ends_at_1() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ]
+        .iter()
+        {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+            assert!(easing.apply(0.25) < easing.apply(0.75));
+        }
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn blending_mixes_at_the_brighter_level() {
+        assert_eq!(LED_LOW_RED.blend(&LED_LOW_BLUE, 0.0), LED_LOW_RED);
+        assert_eq!(LED_LOW_RED.blend(&LED_LOW_BLUE, 1.0), LED_LOW_BLUE);
+        let half = LED_LOW_RED.blend(&LED_LOW_BLUE, 0.5);
+        assert_eq!((half.red(), half.blue()), (128, 128));
+
+        // Fading in from off starts dark rather than at the new colour's full brightness
+        let off = LedValue::default();
+        let quarter = off.blend(&LED_RED, 0.25);
+        assert_eq!(quarter.brightness(), LED_RED.brightness());
+        assert_eq!((quarter.red(), quarter.green()), (64, 0));
+    }
+
+    #[test]
+    fn a_fade_goes_by_the_time_not_the_frame_count() {
+        let start = NaiveDate::from_ymd(2020, 7, 1).and_hms(12, 0, 0);
+        let fade = Fade::new(
+            vec![LED_LOW_RED; 2],
+            start,
+            Duration::seconds(1),
+            Easing::Linear,
+        );
+        let mut frame = vec![LedValue::default(); 2];
+        fade.apply(&[LED_LOW_BLUE; 2], start, &mut frame);
+        assert_eq!(frame, vec![LED_LOW_RED; 2]);
+
+        // However many frames were missed the fade is half way after half the time
+        fade.apply(
+            &[LED_LOW_BLUE; 2],
+            start + Duration::milliseconds(500),
+            &mut frame,
+        );
+        assert_eq!((frame[1].red(), frame[1].blue()), (128, 128));
+        assert!(!fade.is_finished(start + Duration::milliseconds(500)));
+
+        fade.apply(&[LED_LOW_BLUE; 2], start + Duration::seconds(3), &mut frame);
+        assert_eq!(frame, vec![LED_LOW_BLUE; 2]);
+        assert!(fade.is_finished(start + Duration::seconds(3)));
+    }
+}
@@ -0,0 +1,42 @@
+use crate::config::Config;
+use crate::display::{DisplayMode, ModeKind, Scene};
+use crate::error::Result;
+use crate::led::{Compositor, LedClock};
+
+/// The clock face over today's pollen colour, with tomorrow's at the bottom if configured
+pub struct ClockMode {
+    led_clock: LedClock,
+}
+
+impl ClockMode {
+    pub fn new(config: &Config) -> ClockMode {
+        let mut mode = ClockMode {
+            led_clock: LedClock::default(),
+        };
+        mode.reconfigure(config);
+        mode
+    }
+}
+
+impl DisplayMode for ClockMode {
+    fn kind(&self) -> ModeKind {
+        ModeKind::Clock
+    }
+
+    fn reconfigure(&mut self, config: &Config) {
+        self.led_clock
+            .show_tomorrow(config.pollen.tomorrow_leds())
+            .set_smooth_hands(config.display.smooth_hands)
+            .set_face(config.display.clock.clone())
+            .set_theme(config.display.theme().unwrap_or_default());
+    }
+
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()> {
+        self.led_clock
+            .set_background(scene.pollen_colour())
+            .set_background_effect(scene.pollen_effect())
+            .set_tomorrow(scene.tomorrow_colour())
+            .draw(scene.now, layers);
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::config::Config;
+use crate::display::{DisplayMode, ModeKind, Scene};
+use crate::error::Result;
+use crate::led::value::{LED_LOW_AQUA, LED_LOW_GREEN, LED_RED};
+use crate::led::{BlendMode, Compositor, LayerKind, LedEffect, LedValue};
+use chrono::{Duration, NaiveDateTime};
+
+/// A quick flash once the time is up
+const FINISHED_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 1_000,
+    floor: 0.0,
+};
+
+/// How many LEDs the seconds comet trails behind it
+const COMET_TAIL: usize = 3;
+
+/// A timer started when the mode is switched to. The ring starts full and empties
+/// anticlockwise towards the top, the last LED fading out smoothly, while a comet goes round
+/// once a minute to show it is running.
+pub struct CountdownMode {
+    duration: Duration,
+    started: Option<NaiveDateTime>,
+}
+
+impl CountdownMode {
+    pub fn new(duration: Duration) -> CountdownMode {
+        CountdownMode {
+            duration,
+            started: None,
+        }
+    }
+}
+
+impl DisplayMode for CountdownMode {
+    fn kind(&self) -> ModeKind {
+        ModeKind::Countdown
+    }
+
+    fn activate(&mut self, now: NaiveDateTime) {
+        self.started = Some(now);
+    }
+
+    fn reconfigure(&mut self, config: &Config) {
+        self.duration = Duration::minutes(config.display.countdown_mins as i64);
+    }
+
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()> {
+        let frame = layers.layer(LayerKind::Background);
+        let started = *self.started.get_or_insert(scene.now);
+        let elapsed = (scene.now - started).num_milliseconds() as f64;
+        let remaining = 1.0 - elapsed / self.duration.num_milliseconds() as f64;
+        if remaining <= 0.0 {
+            frame.fill(FINISHED_EFFECT.apply(LED_RED, scene.elapsed_ms()));
+            return Ok(());
+        }
+        // How much of the ring is lit, which is a length so a full ring isn't wrapped round to 0
+        let lit = remaining.min(1.0) * frame.len() as f64;
+        let comet = frame.position(elapsed, 60_000.0);
+        frame
+            .fill(LedValue::default())
+            .set_arc(0.0, lit, LED_LOW_GREEN, BlendMode::Over)
+            .hand(comet, LED_LOW_AQUA, COMET_TAIL, BlendMode::Add);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::PollenShown;
+    use crate::led::{LedArray, LedWritable};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn the_ring_empties_as_time_runs_out() {
+        let start = NaiveDate::from_ymd(2020, 7, 1).and_hms(12, 0, 0);
+        let mut countdown = CountdownMode::new(Duration::minutes(8));
+        countdown.activate(start);
+        let mut layers = Compositor::new(12, 0);
+        let mut frame = LedArray::new(12, 0);
+        let mut draw_at = |now| {
+            let scene = Scene {
+                now,
+                pollen: PollenShown::default(),
+                weather: None,
+            };
+            countdown.draw(&scene, &mut layers).unwrap();
+            layers.composite(&mut frame);
+            frame.as_array().to_vec()
+        };
+
+        assert_eq!(draw_at(start)[1..9], [LED_LOW_GREEN; 8]);
+        // Three minutes in seven and a half LEDs are left, the comet is back at the top
+        let frame = draw_at(start + Duration::minutes(3));
+        assert_eq!(frame[1..7], [LED_LOW_GREEN; 6]);
+        assert_eq!(frame[7].green(), 128);
+        assert_eq!(frame[8], LedValue::default());
+        assert_eq!((frame[0].green(), frame[0].blue()), (255, 255));
+        assert_eq!(frame[11].blue(), 191);
+        assert_eq!(draw_at(start + Duration::minutes(9))[0].red(), 255);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::display::{DisplayMode, ModeKind, Scene};
+use crate::error::Result;
+use crate::led::{BlendMode, Compositor, LayerKind, LedValue};
+use crate::pollen::PollenCount;
+
+/// Today's pollen count as a gauge, the ring fills clockwise from the top a quarter for each
+/// level
+pub struct PollenGaugeMode;
+
+impl DisplayMode for PollenGaugeMode {
+    fn kind(&self) -> ModeKind {
+        ModeKind::Pollen
+    }
+
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()> {
+        let filled = match scene.pollen.today {
+            Some(PollenCount::Low) => 0.25,
+            Some(PollenCount::Medium) => 0.5,
+            Some(PollenCount::High) => 0.75,
+            Some(PollenCount::VeryHigh) | Some(PollenCount::NotAvailable) => 1.0,
+            None => 0.0,
+        };
+        let colour = scene
+            .pollen_effect()
+            .apply(scene.pollen_colour(), scene.elapsed_ms());
+        let frame = layers.layer(LayerKind::Background);
+        let lit = (filled * frame.len() as f64).round() as usize;
+        frame
+            .set_background(LedValue::default())
+            .reset()
+            .fill_segment(0, lit, colour, BlendMode::Over);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::PollenShown;
+    use crate::led::{LedArray, LedWritable};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn the_gauge_fills_a_quarter_for_each_level() {
+        let mut layers = Compositor::new(24, 12);
+        let scene = Scene {
+            now: NaiveDate::from_ymd(2020, 7, 1).and_hms(12, 0, 0),
+            pollen: PollenShown {
+                today: Some(PollenCount::Medium),
+                ..Default::default()
+            },
+            weather: None,
+        };
+        PollenGaugeMode.draw(&scene, &mut layers).unwrap();
+        let mut frame = LedArray::new(24, 12);
+        layers.composite(&mut frame);
+        let medium: LedValue = Some(PollenCount::Medium).into();
+        let lit: Vec<usize> = (0..24)
+            .filter(|led| frame.as_array()[*led] == medium)
+            .collect();
+        assert_eq!(lit, (12..24).collect::<Vec<usize>>());
+    }
+}
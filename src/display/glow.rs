@@ -0,0 +1,30 @@
+use crate::display::{DisplayMode, ModeKind, Scene};
+use crate::error::Result;
+use crate::led::value::LED_LOW_WARM;
+use crate::led::{Compositor, LayerKind, LedEffect};
+
+/// A long, shallow breath so the glow is alive without catching the eye
+const GLOW_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 12_000,
+    floor: 0.5,
+};
+
+/// The whole ring glowing softly in today's pollen colour, or candle light without a forecast
+pub struct GlowMode;
+
+impl DisplayMode for GlowMode {
+    fn kind(&self) -> ModeKind {
+        ModeKind::Glow
+    }
+
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()> {
+        let colour = match scene.pollen.today {
+            Some(_) => scene.pollen_colour(),
+            None => LED_LOW_WARM,
+        };
+        layers
+            .layer(LayerKind::Background)
+            .fill(GLOW_EFFECT.apply(colour, scene.elapsed_ms()));
+        Ok(())
+    }
+}
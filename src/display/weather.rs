@@ -0,0 +1,184 @@
+use crate::config::{Config, WeatherConfig, WeatherMeasure};
+use crate::display::{DisplayMode, ModeKind, Scene};
+use crate::error::Result;
+use crate::led::value::LED_LOW_ICE;
+use crate::led::{BlendMode, Compositor, LayerKind, LedEffect, LedValue};
+use crate::met_api::SaneForecastUnit;
+use chrono::{Duration, Local, TimeZone, Utc};
+
+/// A slow breath on the top LED while there is no forecast to show
+const WAITING_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 4_000,
+    floor: 0.1,
+};
+
+/// How far ahead the ring shows, once round
+const HOURS_AHEAD: i64 = 24;
+
+/// Temperatures go from blue when cold to red when hot
+const COLD_HUE: f64 = 240.0;
+
+/// The chance of rain is shown in a blue that gets brighter the more likely it is
+const RAIN_HUE: f64 = 210.0;
+
+/// How light the blue is for a dry hour, so it can be told apart from an hour with no
+/// forecast
+const DRY_LIGHTNESS: f64 = 0.1;
+
+/// The weather over the next day, starting from now at the top and going clockwise an hour
+/// for each LED on a ring of 24
+pub struct WeatherMode {
+    measure: WeatherMeasure,
+    cold_c: f64,
+    hot_c: f64,
+}
+
+impl WeatherMode {
+    pub fn new(config: &WeatherConfig) -> WeatherMode {
+        WeatherMode {
+            measure: config.show,
+            cold_c: config.cold_c,
+            hot_c: config.hot_c,
+        }
+    }
+
+    fn value(&self, unit: &SaneForecastUnit) -> f64 {
+        match self.measure {
+            WeatherMeasure::Temperature => unit.temperature,
+            WeatherMeasure::Precipitation => unit.precipitation_probability,
+        }
+    }
+
+    fn colour(&self, value: f64) -> Result<LedValue> {
+        match self.measure {
+            WeatherMeasure::Temperature => {
+                let warmth = ((value - self.cold_c) / (self.hot_c - self.cold_c)).clamp(0.0, 1.0);
+                LedValue::from_hsl(1, COLD_HUE * (1.0 - warmth), 1.0, 0.5)
+            }
+            WeatherMeasure::Precipitation => {
+                let chance = (value / 100.0).clamp(0.0, 1.0);
+                LedValue::from_hsl(
+                    1,
+                    RAIN_HUE,
+                    1.0,
+                    DRY_LIGHTNESS + (0.5 - DRY_LIGHTNESS) * chance,
+                )
+            }
+        }
+    }
+}
+
+impl DisplayMode for WeatherMode {
+    fn kind(&self) -> ModeKind {
+        ModeKind::Weather
+    }
+
+    fn reconfigure(&mut self, config: &Config) {
+        *self = WeatherMode::new(&config.weather);
+    }
+
+    fn draw(&mut self, scene: &Scene, layers: &mut Compositor) -> Result<()> {
+        let frame = layers.layer(LayerKind::Background);
+        frame.fill(LedValue::default());
+        let now = Local
+            .from_local_datetime(&scene.now)
+            .earliest()
+            .map(|now| now.with_timezone(&Utc));
+        let mut shown = false;
+        if let (Some(forecast), Some(now)) = (scene.weather, now) {
+            for hour in 0..HOURS_AHEAD {
+                let time = now + Duration::hours(hour);
+                if let Some(value) = forecast.at(time, |unit| self.value(unit)) {
+                    let from = frame.position(hour as f64, HOURS_AHEAD as f64);
+                    let to = frame.position((hour + 1) as f64, HOURS_AHEAD as f64);
+                    frame.set_arc(from, to, self.colour(value)?, BlendMode::Over);
+                    shown = true;
+                }
+            }
+        }
+        if !shown {
+            frame.set(0, WAITING_EFFECT.apply(LED_LOW_ICE, scene.elapsed_ms()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::PollenShown;
+    use crate::led::LedArray;
+    use crate::met_api::{SaneForecast, SaneLocation};
+
+    fn forecast() -> SaneForecast {
+        let noon = Utc.ymd(2020, 7, 1).and_hms(12, 0, 0);
+        let unit =
+            |hours: i64, temperature: f64, precipitation_probability: f64| SaneForecastUnit {
+                time: noon + Duration::hours(hours),
+                temperature,
+                precipitation_probability,
+            };
+        SaneForecast {
+            location: SaneLocation {
+                id: 1,
+                name: "TEST".to_string(),
+            },
+            issued_at: noon,
+            units: vec![
+                unit(0, 25.0, 0.0),
+                unit(3, 22.0, 60.0),
+                unit(6, 16.0, 100.0),
+                unit(9, 10.0, 50.0),
+            ],
+        }
+    }
+
+    fn draw(mode: &mut WeatherMode, forecast: Option<&SaneForecast>) -> LedArray {
+        let noon = Utc.ymd(2020, 7, 1).and_hms(12, 0, 0);
+        let scene = Scene {
+            now: noon.with_timezone(&Local).naive_local(),
+            pollen: PollenShown::default(),
+            weather: forecast,
+        };
+        let mut layers = Compositor::new(24, 0);
+        mode.draw(&scene, &mut layers).unwrap();
+        let mut frame = LedArray::new(24, 0);
+        layers.composite(&mut frame);
+        frame
+    }
+
+    #[test]
+    fn each_led_is_an_hour_of_the_forecast() {
+        let forecast = forecast();
+        let mut temperature = WeatherMode::new(&WeatherConfig::default());
+        let frame = draw(&mut temperature, Some(&forecast));
+        let rgb = |led: isize| (frame.get(led).red(), frame.get(led).blue());
+        // Red hot at the top, cooler colours as the evening goes on
+        assert_eq!(rgb(0), (255, 0));
+        assert_eq!(frame.get(6), temperature.colour(16.0).unwrap());
+        assert_eq!(frame.get(9), temperature.colour(10.0).unwrap());
+        // The last forecast stands for the next hour, then there's nothing
+        assert_eq!(frame.get(10), frame.get(9));
+        assert_eq!(frame.get(11), LedValue::default());
+
+        let mut rain = WeatherMode::new(&WeatherConfig {
+            show: WeatherMeasure::Precipitation,
+            ..Default::default()
+        });
+        let frame = draw(&mut rain, Some(&forecast));
+        // Dry, but still lit
+        assert_eq!(frame.get(0), rain.colour(0.0).unwrap());
+        assert!(frame.get(0).blue() > 0);
+        assert_eq!(frame.get(3), rain.colour(60.0).unwrap());
+        assert!(frame.get(2).blue() > frame.get(1).blue());
+        assert_eq!(frame.get(6).blue(), 255);
+    }
+
+    #[test]
+    fn without_a_forecast_it_waits() {
+        let mut mode = WeatherMode::new(&WeatherConfig::default());
+        let frame = draw(&mut mode, None);
+        assert_ne!(frame.get(0), LedValue::default());
+        assert!((1..24).all(|led| frame.get(led) == LedValue::default()));
+    }
+}
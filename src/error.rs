@@ -11,6 +11,10 @@ pub type Result<T> = anyhow::Result<T>;
 pub enum FlowerError {
     #[error("An error occurred `{0}`")]
     SimpleError(String),
+    #[error("Invalid configuration: {0}")]
+    ConfigError(String),
+    #[error("Stopped before finishing")]
+    Stopped,
 }
 
 impl From<PollenParseError> for FlowerError {
@@ -31,31 +35,32 @@ struct ErrorMessage {
 
 impl From<&AnyHowError> for ErrorMessage {
     fn from(error: &AnyHowError) -> Self {
-        match error {
-            _ => ErrorMessage {
-                value1: Some(format!("{:?}", error)),
-                ..Default::default()
-            },
+        ErrorMessage {
+            value1: Some(format!("{:?}", error)),
+            ..Default::default()
         }
     }
 }
 
 pub struct ErrorHandler {
-    hook_uri: String,
+    hook_uri: Option<String>,
 }
 
 impl ErrorHandler {
-    pub fn new(ifttt_key: &str) -> ErrorHandler {
+    /// Without an IFTTT key errors are only written to stderr
+    pub fn new(ifttt_key: Option<&str>) -> ErrorHandler {
         ErrorHandler {
-            hook_uri: format!(
-                "https://maker.ifttt.com/trigger/flower/with/key/{}",
-                ifttt_key
-            ),
+            hook_uri: ifttt_key
+                .map(|key| format!("https://maker.ifttt.com/trigger/flower/with/key/{}", key)),
         }
     }
 
     fn send_message(&self, message: ErrorMessage) -> Result<()> {
-        Request::post(&self.hook_uri)
+        let hook_uri = match &self.hook_uri {
+            Some(hook_uri) => hook_uri,
+            None => return Ok(()),
+        };
+        Request::post(hook_uri)
             .header("Content-Type", "application/json")
             .body(json!(message).to_string())?
             .send()?;
@@ -1,11 +1,24 @@
-// mod array;
+mod array;
+mod backend;
+mod chipset;
 mod clock;
+mod colour;
+mod compositor;
+mod effect;
 mod interface;
-mod value;
+pub mod test_pattern;
+pub mod value;
 
 pub type LedMessage = [u8; 4];
 
-// pub use array::LedArray;
-pub use clock::LedClock;
-pub use interface::{LedInterface, LedWritable};
+pub use array::{BlendMode, LedArray};
+#[cfg(test)]
+pub use backend::RecordingBackend;
+pub use backend::{LedBackend, SpiBackend, TerminalBackend};
+pub use chipset::{Chipset, ColourOrder};
+pub use clock::{ClockFace, ClockTheme, LedClock};
+pub use colour::{Colour, ColourCorrection, WhiteBalance};
+pub use compositor::{Compositor, LayerKind, LayerStyle};
+pub use effect::LedEffect;
+pub use interface::{FillPolicy, LedInterface, LedWritable};
 pub use value::LedValue;
@@ -1,36 +1,208 @@
-use crate::led::{LedMessage, LedValue, LedWritable};
-use crate::{DumbError, Result};
+use crate::led::{LedValue, LedWritable};
+use serde::Deserialize;
 
-const NULL_MESSAGE: LedMessage = [0, 0, 0, 0];
+/// How a colour is drawn onto what is already there
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Covers what is below
+    #[default]
+    Over,
+    /// Adds its light to what is below
+    Add,
+    /// Tints what is below
+    Multiply,
+}
+
+impl BlendMode {
+    /// `value` drawn on `below` with `amount` of its full strength, from 0.0 to 1.0
+    pub fn apply(self, below: LedValue, value: LedValue, amount: f64) -> LedValue {
+        let full = match self {
+            Self::Over => value,
+            Self::Add => below.add(&value),
+            Self::Multiply => below.multiply(&value),
+        };
+        below.blend(&full, amount)
+    }
+}
 
+/// A frame for the ring. Positions are counted clockwise from the LED at the top and wrap
+/// round, so -1 is the LED just before the top and `len()` is the top again.
+///
+/// Each LED also has a coverage from 0.0 to 1.0 so an array can be a layer that is only drawn
+/// in places, anything set or filled is fully covered.
 pub struct LedArray {
     background: LedValue,
+    offset: usize,
     led_buffer: Vec<LedValue>,
+    coverage: Vec<f64>,
 }
 
 impl LedArray {
-    pub fn new(size: usize) -> LedArray {
+    pub fn new(size: usize, offset: usize) -> LedArray {
         LedArray {
             background: LedValue::default(),
+            offset,
             led_buffer: vec![LedValue::default(); size],
+            coverage: vec![1.0; size],
         }
     }
 
+    /// An array with nothing drawn on it yet, for a layer
+    pub fn transparent(size: usize, offset: usize) -> LedArray {
+        let mut array = LedArray::new(size, offset);
+        array.clear();
+        array
+    }
+
+    pub fn len(&self) -> usize {
+        self.led_buffer.len()
+    }
+
     pub fn set_background(&mut self, background: LedValue) -> &mut Self {
         self.background = background;
         self
     }
 
+    /// Fills every LED with the background
     pub fn reset(&mut self) -> &mut Self {
-        self.led_buffer = vec![self.background.clone(); self.led_buffer.len()];
+        let background = self.background;
+        self.fill(background)
+    }
+
+    pub fn fill(&mut self, value: LedValue) -> &mut Self {
+        self.led_buffer.iter_mut().for_each(|led| *led = value);
+        self.coverage
+            .iter_mut()
+            .for_each(|coverage| *coverage = 1.0);
+        self
+    }
+
+    /// Makes every LED transparent
+    pub fn clear(&mut self) -> &mut Self {
+        self.led_buffer
+            .iter_mut()
+            .for_each(|led| *led = LedValue::default());
+        self.coverage
+            .iter_mut()
+            .for_each(|coverage| *coverage = 0.0);
+        self
+    }
+
+    /// The index in the buffer of the LED at `position`
+    pub fn index(&self, position: isize) -> usize {
+        let len = self.len() as isize;
+        ((position + self.offset as isize).rem_euclid(len)) as usize
+    }
+
+    /// The position `amount` of the way round the ring, where `per_turn` is once round, in LEDs
+    /// from the top. It wraps round to between 0.0 and `len()`, and multiplies before dividing
+    /// so an amount that lands on an LED gives exactly that LED whatever the size of the ring.
+    pub fn position(&self, amount: f64, per_turn: f64) -> f64 {
+        let len = self.len() as f64;
+        let position = (amount * len / per_turn).rem_euclid(len);
+        // A tiny negative amount can round up to the length itself
+        if position < len {
+            position
+        } else {
+            0.0
+        }
+    }
+
+    pub fn get(&self, position: isize) -> LedValue {
+        self.led_buffer[self.index(position)]
+    }
+
+    /// How much of the LED at `position` has been drawn on, from 0.0 to 1.0
+    pub fn coverage(&self, position: isize) -> f64 {
+        self.coverage[self.index(position)]
+    }
+
+    pub fn set(&mut self, position: isize, value: LedValue) -> &mut Self {
+        let index = self.index(position);
+        self.led_buffer[index] = value;
+        self.coverage[index] = 1.0;
+        self
+    }
+
+    /// Draws `value` on the LED at `position` with `amount` of its strength. On an LED that
+    /// is only partly covered the value shows through in proportion.
+    pub fn draw(
+        &mut self,
+        position: isize,
+        value: LedValue,
+        amount: f64,
+        mode: BlendMode,
+    ) -> &mut Self {
+        let index = self.index(position);
+        let below = self.coverage[index];
+        let coverage = below + amount.clamp(0.0, 1.0) * (1.0 - below);
+        if coverage <= 0.0 {
+            return self;
+        }
+        if below > 0.0 {
+            let led = self.led_buffer[index];
+            self.led_buffer[index] = mode.apply(led, value, amount / coverage);
+        } else {
+            self.led_buffer[index] = value;
+        }
+        self.coverage[index] = coverage;
+        self
+    }
+
+    /// Draws `count` whole LEDs starting at `first`
+    pub fn fill_segment(
+        &mut self,
+        first: isize,
+        count: usize,
+        value: LedValue,
+        mode: BlendMode,
+    ) -> &mut Self {
+        for led in 0..count.min(self.len()) {
+            self.draw(first + led as isize, value, 1.0, mode);
+        }
         self
     }
 
-    pub fn set_led(&mut self, led_num: usize, value: LedValue) -> Result<&mut Self> {
-        self.led_buffer.get_mut(led_num).map(|led| *led = value).ok_or_else(|| {
-            DumbError(format!("Invalid index {} in LedArray of size {}", led_num, self.led_buffer.len()))
-        })?;
-        Ok(self)
+    /// Draws clockwise from `from` to `to`, measured in LEDs from the top. LEDs the ends only
+    /// partly cover are drawn with that part of the strength.
+    pub fn set_arc(&mut self, from: f64, to: f64, value: LedValue, mode: BlendMode) -> &mut Self {
+        let len = self.len() as f64;
+        let to = if to < from { to + len } else { to };
+        let to = to.min(from + len);
+        let mut led = from.floor();
+        while led < to {
+            let covered = (led + 1.0).min(to) - led.max(from);
+            self.draw(led as isize, value, covered, mode);
+            led += 1.0;
+        }
+        self
+    }
+
+    /// A hand pointing at `position`, in LEDs from the top. A hand between two LEDs is shared
+    /// between them. The tail fades out over `tail` LEDs behind it, like a comet.
+    pub fn hand(
+        &mut self,
+        position: f64,
+        value: LedValue,
+        tail: usize,
+        mode: BlendMode,
+    ) -> &mut Self {
+        let first = position.floor();
+        let share = position - first;
+        let first = first as isize;
+        for behind in (1..=tail).rev() {
+            let strength = 1.0 - behind as f64 / (tail + 1) as f64;
+            self.draw(
+                first - behind as isize,
+                value,
+                strength * (1.0 - share),
+                mode,
+            );
+            self.draw(first - behind as isize + 1, value, strength * share, mode);
+        }
+        self.draw(first, value, 1.0 - share, mode)
+            .draw(first + 1, value, share, mode)
     }
 }
 
@@ -39,3 +211,92 @@ impl LedWritable for LedArray {
         self.led_buffer.as_slice()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::value::{LED_LOW_BLUE, LED_LOW_GREEN, LED_LOW_RED};
+
+    #[test]
+    fn positions_wrap_round_from_the_top() {
+        let mut array = LedArray::new(12, 6);
+        assert_eq!(array.index(0), 6);
+        assert_eq!(array.index(6), 0);
+        assert_eq!(array.index(-1), 5);
+        assert_eq!(array.index(25), 7);
+        array.set(-1, LED_LOW_RED);
+        assert_eq!(array.as_array()[5], LED_LOW_RED);
+        assert_eq!(array.get(11), LED_LOW_RED);
+    }
+
+    #[test]
+    fn positions_round_the_ring_are_exact_and_wrap() {
+        let array = LedArray::new(60, 0);
+        assert_eq!(array.position(35.0, 60.0), 35.0);
+        assert_eq!(array.position(7.0, 12.0), 35.0);
+        assert_eq!(array.position(1.0, 1.0), 0.0);
+        assert_eq!(array.position(-15.0, 60.0), 45.0);
+        assert_eq!(array.position(-1e-20, 1.0), 0.0);
+        let odd = LedArray::new(13, 0);
+        assert_eq!(odd.position(1.0, 2.0), 6.5);
+        assert_eq!(odd.position(25.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn segments_and_arcs_wrap_past_the_top() {
+        let mut array = LedArray::new(8, 0);
+        array.fill_segment(-1, 3, LED_LOW_RED, BlendMode::Over);
+        let red: Vec<usize> = (0..8)
+            .filter(|led| array.as_array()[*led] == LED_LOW_RED)
+            .collect();
+        assert_eq!(red, vec![0, 1, 7]);
+
+        // From half way through LED 6 to a quarter of the way through LED 1
+        array
+            .reset()
+            .set_arc(6.5, 1.25, LED_LOW_BLUE, BlendMode::Over);
+        let blue: Vec<u8> = array.as_array().iter().map(|led| led.blue()).collect();
+        assert_eq!(blue, vec![255, 64, 255, 255, 255, 255, 128, 255]);
+        assert_eq!(array.get(3), LedValue::default());
+    }
+
+    #[test]
+    fn a_hand_has_a_fading_tail() {
+        let mut array = LedArray::new(12, 0);
+        array.hand(0.0, LED_LOW_GREEN, 2, BlendMode::Over);
+        let green: Vec<u8> = array.as_array().iter().map(|led| led.green()).collect();
+        assert_eq!(green[0], 255);
+        assert_eq!(green[11], 170);
+        assert_eq!(green[10], 85);
+        assert_eq!(array.get(1), LedValue::default());
+    }
+
+    #[test]
+    fn drawing_on_a_transparent_array_covers_it_by_the_amount() {
+        let mut layer = LedArray::transparent(4, 0);
+        assert_eq!(layer.coverage(0), 0.0);
+        layer.draw(0, LED_LOW_BLUE, 0.25, BlendMode::Add);
+        assert_eq!(layer.get(0), LED_LOW_BLUE);
+        assert_eq!(layer.coverage(0), 0.25);
+        // Half of what's left, so the new colour is two thirds of what is there now
+        layer.draw(0, LED_LOW_RED, 0.5, BlendMode::Over);
+        assert_eq!(layer.coverage(0), 0.625);
+        assert_eq!((layer.get(0).red(), layer.get(0).blue()), (204, 51));
+        assert_eq!(layer.set(1, LED_LOW_RED).coverage(1), 1.0);
+    }
+
+    #[test]
+    fn blend_modes_combine_differently() {
+        let below = LED_LOW_RED;
+        assert_eq!(
+            BlendMode::Over.apply(below, LED_LOW_BLUE, 1.0),
+            LED_LOW_BLUE
+        );
+        let added = BlendMode::Add.apply(below, LED_LOW_BLUE, 1.0);
+        assert_eq!((added.red(), added.blue()), (255, 255));
+        let tinted = BlendMode::Multiply.apply(below, LED_LOW_BLUE, 1.0);
+        assert_eq!((tinted.red(), tinted.blue()), (0, 0));
+        let half = BlendMode::Add.apply(below, LED_LOW_BLUE, 0.5);
+        assert_eq!((half.red(), half.blue()), (255, 128));
+    }
+}
@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod recording;
+mod spi;
+mod terminal;
+
+use crate::error::Result;
+use crate::led::LedValue;
+
+#[cfg(test)]
+pub use recording::RecordingBackend;
+#[cfg(test)]
+pub use spi::CapturingTransport;
+pub use spi::SpiBackend;
+pub use terminal::TerminalBackend;
+
+/// Something that can put a frame of LEDs on display, be that real hardware or otherwise
+pub trait LedBackend {
+    /// Shows `frame` with the whole ring giving `level` of its light, from 0.0 to 1.0
+    fn write_frame(&mut self, frame: &[LedValue], level: f64) -> Result<()>;
+}
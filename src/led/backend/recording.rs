@@ -0,0 +1,35 @@
+use crate::error::Result;
+use crate::led::backend::LedBackend;
+use crate::led::LedValue;
+use std::sync::{Arc, Mutex};
+
+/// Keeps every frame it is given, dimmed to the level it was given at, so tests can inspect
+/// what would have been displayed.
+///
+/// Clones share the same recording, so keep a clone before boxing the backend into an interface.
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    frames: Arc<Mutex<Vec<Vec<LedValue>>>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> RecordingBackend {
+        RecordingBackend::default()
+    }
+
+    pub fn frames(&self) -> Vec<Vec<LedValue>> {
+        self.frames.lock().unwrap().clone()
+    }
+
+    pub fn last_frame(&self) -> Option<Vec<LedValue>> {
+        self.frames.lock().unwrap().last().cloned()
+    }
+}
+
+impl LedBackend for RecordingBackend {
+    fn write_frame(&mut self, frame: &[LedValue], level: f64) -> Result<()> {
+        let dimmed = frame.iter().map(|value| value.dimmed(level)).collect();
+        self.frames.lock().unwrap().push(dimmed);
+        Ok(())
+    }
+}
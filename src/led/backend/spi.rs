@@ -0,0 +1,199 @@
+use crate::config::{LedConfig, SpiConfig};
+use crate::error::{FlowerError, Result};
+use crate::led::backend::LedBackend;
+use crate::led::{Chipset, ColourCorrection, ColourOrder, LedValue};
+use rppal::spi::Spi;
+
+/// Where the encoded bytes go, the SPI bus or something standing in for it
+pub trait Transport {
+    fn write(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+impl Transport for Spi {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        let written = Spi::write(self, bytes)?;
+        if written < bytes.len() {
+            return Err(FlowerError::SimpleError(format!(
+                "only {} of {} bytes were written to SPI",
+                written,
+                bytes.len()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Writes frames to an APA102 or SK9822 strip over SPI. Colours are corrected for the LEDs and
+/// then dimmed, so the level is a share of the light rather than of the colour on a screen.
+/// Each frame goes out in a single transfer, encoded into buffers kept between frames.
+pub struct SpiBackend {
+    transport: Box<dyn Transport>,
+    chipset: Chipset,
+    colour_order: ColourOrder,
+    correction: ColourCorrection,
+    corrected: Vec<LedValue>,
+    buffer: Vec<u8>,
+}
+
+impl SpiBackend {
+    pub fn new(config: &SpiConfig, leds: &LedConfig) -> Result<SpiBackend> {
+        Ok(SpiBackend::with_transport(Box::new(config.open()?), leds))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>, leds: &LedConfig) -> SpiBackend {
+        SpiBackend {
+            transport,
+            chipset: leds.chipset,
+            colour_order: leds.colour_order,
+            correction: leds.colour_correction(),
+            corrected: Vec::with_capacity(leds.count),
+            buffer: Vec::with_capacity(4 * leds.count + 16),
+        }
+    }
+}
+
+impl LedBackend for SpiBackend {
+    fn write_frame(&mut self, frame: &[LedValue], level: f64) -> Result<()> {
+        let correction = self.correction;
+        self.corrected.clear();
+        self.corrected.extend(
+            frame
+                .iter()
+                .map(|value| correction.apply(value).dimmed(level)),
+        );
+        self.chipset
+            .encode(&self.corrected, self.colour_order, &mut self.buffer);
+        self.transport.write(&self.buffer)
+    }
+}
+
+/// Keeps the bytes of every transfer so tests can check what would go down the wire.
+///
+/// Clones share the same capture, so keep a clone before boxing the transport into a backend.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct CapturingTransport {
+    transfers: std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl CapturingTransport {
+    pub fn transfers(&self) -> Vec<Vec<u8>> {
+        self.transfers.lock().unwrap().clone()
+    }
+
+    pub fn last_transfer(&self) -> Option<Vec<u8>> {
+        self.transfers.lock().unwrap().last().cloned()
+    }
+}
+
+#[cfg(test)]
+impl Transport for CapturingTransport {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.transfers.lock().unwrap().push(bytes.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::backend::RecordingBackend;
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::time::Instant;
+
+    /// Real writes, so each one costs a syscall like it would on the Pi
+    struct DevNull(File);
+
+    impl Transport for DevNull {
+        fn write(&mut self, bytes: &[u8]) -> Result<()> {
+            self.0.write_all(bytes)?;
+            Ok(())
+        }
+    }
+
+    fn dev_null() -> DevNull {
+        DevNull(OpenOptions::new().write(true).open("/dev/null").unwrap())
+    }
+
+    #[test]
+    fn each_frame_is_a_single_transfer() {
+        let capturing = CapturingTransport::default();
+        let mut backend =
+            SpiBackend::with_transport(Box::new(capturing.clone()), &LedConfig::default());
+        let frame = vec![LedValue::default(); 24];
+        backend.write_frame(&frame, 1.0).unwrap();
+        backend.write_frame(&frame, 1.0).unwrap();
+        // 4 byte start frame, 24 LEDs of 4 bytes and a 4 byte end frame
+        let lengths: Vec<usize> = capturing.transfers().iter().map(Vec::len).collect();
+        assert_eq!(lengths, vec![104, 104]);
+    }
+
+    #[test]
+    fn dimming_is_a_share_of_the_light_after_gamma() {
+        let capturing = CapturingTransport::default();
+        let leds = LedConfig {
+            count: 1,
+            gamma: true,
+            ..Default::default()
+        };
+        let mut backend = SpiBackend::with_transport(Box::new(capturing.clone()), &leds);
+        let sent = |backend: &mut SpiBackend, value: LedValue, level: f64| {
+            backend.write_frame(&[value], level).unwrap();
+            capturing.last_transfer().unwrap()[4..8].to_vec()
+        };
+        // sRGB 188 is 128 on the LEDs, a third of the light is a third of that
+        let grey = LedValue::new(1, 188, 188, 188).unwrap();
+        assert_eq!(sent(&mut backend, grey, 1.0), vec![0xE1, 128, 128, 128]);
+        assert_eq!(sent(&mut backend, grey, 1.0 / 3.0), vec![0xE1, 43, 43, 43]);
+        // A faint channel stays lit rather than changing the colour
+        assert_eq!(
+            sent(&mut backend, grey.with_channels(255, 40, 0), 0.1)[1..],
+            [0, 1, 26]
+        );
+        // Brighter LEDs step down their own brightness first
+        let bright = LedValue::new(31, 255, 255, 255).unwrap();
+        assert_eq!(sent(&mut backend, bright, 0.3), vec![0xEA, 237, 237, 237]);
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture frame_write_benchmark`
+    #[test]
+    #[ignore]
+    fn frame_write_benchmark() {
+        const FRAMES: u32 = 10_000;
+        let leds = LedConfig::default();
+        let frame = vec![LedValue::new(1, 255, 150, 0).unwrap(); leds.count];
+        let time = |name: &str, write: &mut dyn FnMut()| {
+            let started = Instant::now();
+            for _ in 0..FRAMES {
+                write();
+            }
+            println!("{:>22}: {:?} per frame", name, started.elapsed() / FRAMES);
+        };
+
+        let mut recording = RecordingBackend::new();
+        time("recording backend", &mut || {
+            recording.write_frame(&frame, 1.0).unwrap()
+        });
+
+        let mut per_led = dev_null();
+        time("one write per LED", &mut || {
+            per_led.write(&[0; 4]).unwrap();
+            for value in frame.iter() {
+                per_led
+                    .write(&leds.chipset.led_frame(value, leds.colour_order))
+                    .unwrap();
+            }
+            per_led
+                .write(&vec![0; leds.chipset.end_frame_len(frame.len())])
+                .unwrap();
+        });
+
+        let mut batched = SpiBackend::with_transport(Box::new(dev_null()), &leds);
+        time("one write per frame", &mut || {
+            batched.write_frame(&frame, 1.0).unwrap()
+        });
+    }
+}
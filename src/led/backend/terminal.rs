@@ -0,0 +1,98 @@
+use crate::error::Result;
+use crate::led::backend::LedBackend;
+use crate::led::LedValue;
+use std::f64::consts::PI;
+use std::io::{stdout, Stdout, Write};
+
+const CLEAR_SCREEN: &str = "\x1b[2J";
+const CURSOR_HOME: &str = "\x1b[H";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const RESET_COLOUR: &str = "\x1b[0m";
+
+/// The colour an unlit LED is drawn in so the shape of the ring is still visible
+const UNLIT: (u8, u8, u8) = (48, 48, 48);
+
+/// Draws the ring as a circle of true colour blocks in an ANSI terminal
+pub struct TerminalBackend {
+    top_led: usize,
+    out: Stdout,
+}
+
+impl TerminalBackend {
+    /// `top_led` is the index of the LED that sits at 12 o'clock on the physical ring
+    pub fn new(top_led: usize) -> Result<TerminalBackend> {
+        let mut out = stdout();
+        write!(out, "{}{}", CLEAR_SCREEN, HIDE_CURSOR)?;
+        out.flush()?;
+        Ok(TerminalBackend { top_led, out })
+    }
+
+    /// Where each LED sits on a grid of (row, column) cells, going clockwise from `top_led`
+    fn layout(&self, num_leds: usize) -> (usize, Vec<(usize, usize)>) {
+        let radius = (num_leds as f64 / 3.0).max(4.0);
+        let size = (radius * 2.0).round() as usize + 1;
+        let positions = (0..num_leds)
+            .map(|led| {
+                let step = (led + num_leds - self.top_led % num_leds) % num_leds;
+                let angle = 2.0 * PI * step as f64 / num_leds as f64;
+                let row = (radius - radius * angle.cos()).round() as usize;
+                let column = (radius + radius * angle.sin()).round() as usize;
+                (row, column)
+            })
+            .collect();
+        (size, positions)
+    }
+}
+
+/// Scales the colour by the LEDs 0-31 brightness, on a log curve as that is closer to how
+/// the real LEDs look than a straight line
+fn terminal_colour(value: &LedValue) -> (u8, u8, u8) {
+    if value.brightness() == 0 {
+        return UNLIT;
+    }
+    let scale = (1.0 + value.brightness() as f64).ln() / 32f64.ln();
+    let apply = |channel: u8| (channel as f64 * scale).round() as u8;
+    (
+        apply(value.red()),
+        apply(value.green()),
+        apply(value.blue()),
+    )
+}
+
+impl LedBackend for TerminalBackend {
+    fn write_frame(&mut self, frame: &[LedValue], level: f64) -> Result<()> {
+        if frame.is_empty() {
+            return Ok(());
+        }
+        let (size, positions) = self.layout(frame.len());
+        let mut grid: Vec<Vec<Option<(u8, u8, u8)>>> = vec![vec![None; size]; size];
+        for (value, (row, column)) in frame.iter().zip(positions) {
+            grid[row][column] = Some(terminal_colour(&value.dimmed(level)));
+        }
+
+        let mut screen = String::from(CURSOR_HOME);
+        for row in grid {
+            for cell in row {
+                match cell {
+                    Some((red, green, blue)) => screen.push_str(&format!(
+                        "\x1b[38;2;{};{};{}m██{}",
+                        red, green, blue, RESET_COLOUR
+                    )),
+                    None => screen.push_str("  "),
+                }
+            }
+            screen.push('\n');
+        }
+        self.out.write_all(screen.as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalBackend {
+    fn drop(&mut self) {
+        let _ = write!(self.out, "{}{}", RESET_COLOUR, SHOW_CURSOR);
+        let _ = self.out.flush();
+    }
+}
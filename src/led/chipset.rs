@@ -0,0 +1,110 @@
+use crate::led::{LedMessage, LedValue};
+use serde::Deserialize;
+
+/// 32 zero bits before the first LED
+const START_FRAME: LedMessage = [0; 4];
+
+/// Every LED frame starts with three set bits, the other five are the brightness
+const LED_FRAME_HEADER: u8 = 0b1110_0000;
+
+/// The order the colour bytes follow the brightness byte in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColourOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    #[default]
+    Bgr,
+    Brg,
+}
+
+impl ColourOrder {
+    fn arrange(self, value: &LedValue) -> [u8; 3] {
+        let (r, g, b) = (value.red(), value.green(), value.blue());
+        match self {
+            Self::Rgb => [r, g, b],
+            Self::Rbg => [r, b, g],
+            Self::Grb => [g, r, b],
+            Self::Gbr => [g, b, r],
+            Self::Bgr => [b, g, r],
+            Self::Brg => [b, r, g],
+        }
+    }
+}
+
+/// The kind of LED on the ring. They share a frame format but finish a frame differently.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chipset {
+    #[default]
+    Apa102,
+    /// An APA102 clone that also needs a 32 bit reset frame before the end frame
+    Sk9822,
+}
+
+impl Chipset {
+    /// The four bytes for a single LED
+    pub fn led_frame(self, value: &LedValue, order: ColourOrder) -> LedMessage {
+        let [first, second, third] = order.arrange(value);
+        [LED_FRAME_HEADER | value.brightness(), first, second, third]
+    }
+
+    /// How many zero bytes to send after the last LED. Each LED delays the data by half a
+    /// clock so the last LEDs only latch after another `count / 2` clock edges, or 32 if that's
+    /// more.
+    pub fn end_frame_len(self, count: usize) -> usize {
+        let latch = count.div_ceil(16).max(4);
+        match self {
+            Self::Apa102 => latch,
+            Self::Sk9822 => 4 + latch,
+        }
+    }
+
+    /// Everything sent on the wire for one frame, written into `buffer` so that it can be
+    /// reused from frame to frame
+    pub fn encode(self, frame: &[LedValue], order: ColourOrder, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(&START_FRAME);
+        for value in frame {
+            buffer.extend_from_slice(&self.led_frame(value, order));
+        }
+        buffer.resize(buffer.len() + self.end_frame_len(frame.len()), 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_are_sent_in_the_configured_order() {
+        let value = LedValue::new(5, 1, 2, 3).unwrap();
+        let frame = |order| Chipset::Apa102.led_frame(&value, order);
+        assert_eq!(frame(ColourOrder::Bgr), [0xE5, 3, 2, 1]);
+        assert_eq!(frame(ColourOrder::Rgb), [0xE5, 1, 2, 3]);
+        assert_eq!(frame(ColourOrder::Grb), [0xE5, 2, 1, 3]);
+        assert_eq!(frame(ColourOrder::Brg), [0xE5, 3, 1, 2]);
+    }
+
+    #[test]
+    fn end_frames_give_every_led_time_to_latch() {
+        assert_eq!(Chipset::Apa102.end_frame_len(24), 4);
+        // 144 LEDs need 72 clock edges, 9 bytes
+        assert_eq!(Chipset::Apa102.end_frame_len(144), 9);
+        assert_eq!(Chipset::Sk9822.end_frame_len(24), 8);
+        assert_eq!(Chipset::Sk9822.end_frame_len(144), 13);
+    }
+
+    #[test]
+    fn a_whole_frame_is_encoded_into_one_buffer() {
+        let mut buffer = vec![0xFF; 100];
+        let frame = [LedValue::new(1, 10, 20, 30).unwrap(); 2];
+        Chipset::Apa102.encode(&frame, ColourOrder::Bgr, &mut buffer);
+        assert_eq!(
+            buffer,
+            vec![0, 0, 0, 0, 0xE1, 30, 20, 10, 0xE1, 30, 20, 10, 0, 0, 0, 0]
+        );
+    }
+}
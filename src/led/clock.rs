@@ -1,70 +1,436 @@
-use crate::clock::Clock;
-use crate::error::{FlowerError, Result};
-use crate::led::interface::LedWritable;
-use crate::led::LedValue;
-use crate::led::value::{LED_LOW_PURPLE, LED_LOW_BLUE, LED_LOW_AQUA};
+use crate::led::value::{LED_LOW_AQUA, LED_LOW_BLUE, LED_LOW_PURPLE, LED_LOW_WHITE};
+use crate::led::{BlendMode, Colour, Compositor, LayerKind, LedArray, LedEffect, LedValue};
+use chrono::{NaiveDateTime, Timelike};
+use serde::Deserialize;
 
-pub struct LedClock {
-    clock: Clock,
-    background: LedValue,
-    hour: LedValue,
-    minute: LedValue,
-    second: LedValue,
-    led_buffer: Vec<LedValue>,
-    led_offset: usize,
+/// The colours of the clock face
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockTheme {
+    pub hour: Colour,
+    pub minute: Colour,
+    pub second: Colour,
+    pub markers: Colour,
 }
 
-impl LedClock {
-    pub fn new(num_leds: usize, led_offset: usize, clock: Clock) -> LedClock {
-        LedClock {
-            clock,
-            led_offset,
-            background: LedValue::default(),
-            hour: LED_LOW_PURPLE.clone(),
-            minute: LED_LOW_BLUE.clone(),
-            second: LED_LOW_AQUA.clone(),
-            led_buffer: vec![LedValue::default(); num_leds],
+impl Default for ClockTheme {
+    /// The classic theme
+    fn default() -> Self {
+        ClockTheme {
+            hour: Colour(LED_LOW_PURPLE),
+            minute: Colour(LED_LOW_BLUE),
+            second: Colour(LED_LOW_AQUA),
+            markers: Colour(LED_LOW_WHITE.scaled(0.42)),
+        }
+    }
+}
+
+impl ClockTheme {
+    /// One of the themes that come with the flower, classic or ember
+    pub fn built_in(name: &str) -> Option<ClockTheme> {
+        let hsl = |hue, saturation, lightness| {
+            Colour(
+                LedValue::from_hsl(1, hue, saturation, lightness)
+                    .expect("a brightness of 1 is always valid"),
+            )
+        };
+        match name {
+            "classic" => Some(ClockTheme::default()),
+            "ember" => Some(ClockTheme {
+                hour: hsl(10.0, 1.0, 0.5),
+                minute: hsl(30.0, 1.0, 0.5),
+                second: hsl(45.0, 1.0, 0.6),
+                markers: hsl(20.0, 0.6, 0.1),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// How the clock face is laid out
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockFace {
+    /// A built in theme or one from `[display.themes]`
+    pub theme: String,
+    /// How many LEDs each hand covers, 0 to hide it
+    pub hour_width: usize,
+    pub minute_width: usize,
+    pub second_width: usize,
+    /// Move the hour hand on as the minutes pass rather than once an hour
+    pub hour_follows_minutes: bool,
+    /// The hour hand goes round once a day rather than twice
+    pub dial_24h: bool,
+    /// Mark 12, 3, 6 and 9 o'clock on the background
+    pub quarter_markers: bool,
+}
+
+impl Default for ClockFace {
+    fn default() -> Self {
+        ClockFace {
+            theme: "classic".to_string(),
+            hour_width: 2,
+            minute_width: 1,
+            second_width: 1,
+            hour_follows_minutes: false,
+            dial_24h: false,
+            quarter_markers: false,
         }
     }
+}
+
+/// Draws the time over a background colour
+#[derive(Default)]
+pub struct LedClock {
+    background: LedValue,
+    background_effect: LedEffect,
+    tomorrow: Option<LedValue>,
+    tomorrow_leds: usize,
+    face: ClockFace,
+    theme: ClockTheme,
+    smooth_hands: bool,
+}
 
+impl LedClock {
     pub fn set_background(&mut self, background: LedValue) -> &mut Self {
         self.background = background;
         self
     }
 
-    fn set_led(&mut self, led_num: usize, value: LedValue) -> Result<&mut Self> {
-        self.led_buffer
-            .get_mut(led_num)
-            .map(|led| *led = value)
-            .ok_or_else(|| {
-                FlowerError::SimpleError(format!(
-                    "Invalid index {} in LedArray of size {}",
-                    led_num,
-                    self.led_buffer.len()
-                ))
-            })?;
-        Ok(self)
+    pub fn set_background_effect(&mut self, effect: LedEffect) -> &mut Self {
+        self.background_effect = effect;
+        self
+    }
+
+    /// How many LEDs at the bottom of the ring show tomorrow's pollen count, 0 to not show it
+    pub fn show_tomorrow(&mut self, leds: usize) -> &mut Self {
+        self.tomorrow_leds = leds;
+        self
+    }
+
+    pub fn set_tomorrow(&mut self, tomorrow: Option<LedValue>) -> &mut Self {
+        self.tomorrow = tomorrow;
+        self
+    }
+
+    pub fn set_face(&mut self, face: ClockFace) -> &mut Self {
+        self.face = face;
+        self
+    }
+
+    pub fn set_theme(&mut self, theme: ClockTheme) -> &mut Self {
+        self.theme = theme;
+        self
     }
 
-    fn fit_index_to_buffer(&self, index: usize, divisor: usize) -> usize {
-        (((index * self.led_buffer.len()) / divisor) + self.led_offset) % self.led_buffer.len()
+    /// Moves the hands continuously, sharing the LEDs at each end of a hand with the
+    /// background, rather than jumping from LED to LED
+    pub fn set_smooth_hands(&mut self, smooth_hands: bool) -> &mut Self {
+        self.smooth_hands = smooth_hands;
+        self
+    }
+
+    /// Draws the clock face at `now`, the background and each hand on their own layer
+    pub fn draw(&self, now: NaiveDateTime, layers: &mut Compositor) {
+        let elapsed_ms =
+            now.num_seconds_from_midnight() as u64 * 1_000 + (now.nanosecond() / 1_000_000) as u64;
+        let background = self.background_effect.apply(self.background, elapsed_ms);
+        let frame = layers.layer(LayerKind::Background);
+        frame.set_background(background).reset();
+        if self.face.quarter_markers {
+            for quarter in 0..4 {
+                let marker = frame.position(quarter as f64, 4.0).floor() as isize;
+                frame.set(marker, self.theme.markers.0);
+            }
+        }
+        if let Some(tomorrow) = self.tomorrow {
+            // A segment centred on 6 o'clock
+            let first =
+                frame.position(1.0, 2.0).floor() as isize - (self.tomorrow_leds / 2) as isize;
+            frame.fill_segment(first, self.tomorrow_leds, tomorrow, BlendMode::Over);
+        }
+
+        // Each hand's position in seconds, whole seconds unless the hands move smoothly
+        let smooth = |seconds: f64| if self.smooth_hands { seconds } else { 0.0 };
+        let second = now.second() as f64 + smooth(now.nanosecond() as f64 / 1e9);
+        let minute = (now.minute() * 60) as f64 + smooth(second);
+        let dial_hours = if self.face.dial_24h { 24 } else { 12 };
+        let mut hour = (now.hour() % dial_hours * 3600) as f64;
+        if self.face.hour_follows_minutes {
+            hour += minute;
+        }
+        let hour = frame.position(hour, (dial_hours * 3600) as f64);
+        let minute = frame.position(minute, 3600.0);
+        let second = frame.position(second, 60.0);
+        self.draw_hand(
+            layers.layer(LayerKind::HourHand),
+            hour,
+            self.face.hour_width,
+            self.theme.hour.0,
+        );
+        self.draw_hand(
+            layers.layer(LayerKind::MinuteHand),
+            minute,
+            self.face.minute_width,
+            self.theme.minute.0,
+        );
+        self.draw_hand(
+            layers.layer(LayerKind::SecondHand),
+            second,
+            self.face.second_width,
+            self.theme.second.0,
+        );
     }
 
-    pub fn update(&mut self) -> Result<&mut Self> {
-        self.led_buffer = vec![self.background.clone(); self.led_buffer.len()];
-        let hours = self.fit_index_to_buffer(self.clock.get_hours(), 12);
-        let minutes = self.fit_index_to_buffer(self.clock.get_minutes(), 60);
-        let seconds = self.fit_index_to_buffer(self.clock.get_seconds(), 60);
-        self.set_led(hours, self.hour)?;
-        self.set_led(hours + 1, self.hour)?;
-        self.set_led(minutes, self.minute)?;
-        self.set_led(seconds, self.second)?;
-        Ok(self)
+    /// A hand `width` LEDs wide starting at `position`, counted in LEDs from the top
+    fn draw_hand(&self, layer: &mut LedArray, position: f64, width: usize, value: LedValue) {
+        if self.smooth_hands {
+            layer.set_arc(position, position + width as f64, value, BlendMode::Over);
+        } else {
+            layer.fill_segment(position.floor() as isize, width, value, BlendMode::Over);
+        }
     }
 }
 
-impl LedWritable for LedClock {
-    fn as_array(&self) -> &[LedValue] {
-        self.led_buffer.as_slice()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::value::LED_LOW_RED;
+    use crate::led::{LedArray, LedWritable};
+    use chrono::{Duration, NaiveDate};
+    use proptest::prelude::*;
+
+    fn at(hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2020, 7, 1).and_hms(hour, minute, second)
+    }
+
+    fn draw_at(led_clock: &LedClock, now: NaiveDateTime) -> LedArray {
+        let mut layers = Compositor::new(24, 12);
+        led_clock.draw(now, &mut layers);
+        let mut frame = LedArray::new(24, 12);
+        layers.composite(&mut frame);
+        frame
+    }
+
+    /// All three hands on one LED, an equal mix of purple, blue and aqua
+    fn all_hands() -> LedValue {
+        LedValue::new(1, 85, 85, 255).unwrap()
+    }
+
+    fn lit(frame: &LedArray) -> Vec<(usize, LedValue)> {
+        frame
+            .as_array()
+            .iter()
+            .cloned()
+            .enumerate()
+            .filter(|(_, led)| *led != LedValue::default())
+            .collect()
+    }
+
+    #[test]
+    fn just_before_midnight() {
+        let led_clock = LedClock::default();
+        assert_eq!(
+            lit(&draw_at(&led_clock, at(23, 59, 59))),
+            vec![(10, LED_LOW_PURPLE), (11, all_hands())]
+        );
+    }
+
+    #[test]
+    fn midnight() {
+        let led_clock = LedClock::default();
+        assert_eq!(
+            lit(&draw_at(&led_clock, at(0, 0, 0))),
+            vec![(12, all_hands()), (13, LED_LOW_PURPLE)]
+        );
+    }
+
+    #[test]
+    fn tomorrow_is_shown_at_the_bottom_of_the_ring() {
+        let mut led_clock = LedClock::default();
+        led_clock.show_tomorrow(3).set_tomorrow(Some(LED_LOW_RED));
+        assert_eq!(
+            lit(&draw_at(&led_clock, at(0, 0, 0))),
+            vec![
+                (0, LED_LOW_RED),
+                (1, LED_LOW_RED),
+                (12, all_hands()),
+                (13, LED_LOW_PURPLE),
+                (23, LED_LOW_RED)
+            ]
+        );
+    }
+
+    #[test]
+    fn hands_follow_the_time() {
+        let led_clock = LedClock::default();
+        let mut now = at(11, 59, 59);
+        assert_eq!(
+            lit(&draw_at(&led_clock, now)),
+            vec![(10, LED_LOW_PURPLE), (11, all_hands())]
+        );
+
+        now += Duration::seconds(1);
+        assert_eq!(
+            lit(&draw_at(&led_clock, now)),
+            vec![(12, all_hands()), (13, LED_LOW_PURPLE)]
+        );
+
+        // The hour and second hands still share the top LED half and half
+        now += Duration::minutes(15);
+        let hour_and_second = LED_LOW_PURPLE.blend(&LED_LOW_AQUA, 0.5);
+        assert_eq!(
+            lit(&draw_at(&led_clock, now)),
+            vec![
+                (12, hour_and_second),
+                (13, LED_LOW_PURPLE),
+                (18, LED_LOW_BLUE)
+            ]
+        );
+
+        assert_eq!(
+            lit(&draw_at(&led_clock, at(6, 30, 0))),
+            vec![
+                (0, LED_LOW_PURPLE.blend(&LED_LOW_BLUE, 0.5)),
+                (1, LED_LOW_PURPLE),
+                (12, LED_LOW_AQUA)
+            ]
+        );
+    }
+
+    #[test]
+    fn the_hour_hand_can_follow_the_minutes_round_a_24_hour_dial() {
+        let mut led_clock = LedClock::default();
+        let hour_hand = |led_clock: &LedClock, now| {
+            let mut layers = Compositor::new(24, 0);
+            led_clock.draw(now, &mut layers);
+            let layer = layers.layer(LayerKind::HourHand);
+            (0..24)
+                .filter(|led| layer.coverage(*led) > 0.0)
+                .collect::<Vec<isize>>()
+        };
+        assert_eq!(hour_hand(&led_clock, at(2, 59, 0)), vec![4, 5]);
+
+        // Nearly three o'clock is nearly at LED 6
+        led_clock.set_face(ClockFace {
+            hour_width: 1,
+            hour_follows_minutes: true,
+            ..Default::default()
+        });
+        assert_eq!(hour_hand(&led_clock, at(2, 59, 0)), vec![5]);
+        assert_eq!(hour_hand(&led_clock, at(3, 0, 0)), vec![6]);
+
+        // Once round a day, so six in the evening is at the bottom
+        led_clock.set_face(ClockFace {
+            dial_24h: true,
+            ..Default::default()
+        });
+        assert_eq!(hour_hand(&led_clock, at(18, 0, 0)), vec![18, 19]);
+        assert_eq!(hour_hand(&led_clock, at(6, 0, 0)), vec![6, 7]);
+    }
+
+    #[test]
+    fn the_face_has_widths_markers_and_a_theme() {
+        let mut led_clock = LedClock::default();
+        let theme = ClockTheme::built_in("ember").unwrap();
+        led_clock
+            .set_face(ClockFace {
+                minute_width: 3,
+                second_width: 0,
+                quarter_markers: true,
+                ..Default::default()
+            })
+            .set_theme(theme);
+        // Positions from the top, the offset is 12. The markers at 12 and 3 are under hands.
+        let mut lit: Vec<(usize, LedValue)> = lit(&draw_at(&led_clock, at(0, 15, 30)))
+            .into_iter()
+            .map(|(index, led)| ((index + 12) % 24, led))
+            .collect();
+        lit.sort_by_key(|(position, _)| *position);
+        assert_eq!(
+            lit,
+            vec![
+                (0, theme.hour.0),
+                (1, theme.hour.0),
+                (6, theme.minute.0),
+                (7, theme.minute.0),
+                (8, theme.minute.0),
+                (12, theme.markers.0),
+                (18, theme.markers.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn smooth_hands_are_shared_between_neighbouring_leds() {
+        let mut led_clock = LedClock::default();
+        led_clock.set_smooth_hands(true);
+        // Five and five eighths minutes is 2.25 LEDs round, mostly on the third LED
+        let frame = draw_at(&led_clock, at(0, 5, 37) + Duration::milliseconds(500));
+        let leds = frame.as_array();
+        assert_eq!(leds[14].blue(), 191);
+        assert_eq!(leds[15].blue(), 64);
+        assert_eq!(leds[16], LedValue::default());
+
+        // Exactly on an LED lights only that LED
+        let frame = draw_at(&led_clock, at(0, 15, 0));
+        assert_eq!(frame.as_array()[18], LED_LOW_BLUE);
+        assert_eq!(frame.as_array()[19], LedValue::default());
+    }
+
+    proptest! {
+        #[test]
+        fn every_time_of_day_fits_any_ring(
+            num_leds in 12usize..=144,
+            offset in 0usize..144,
+            seconds in 0i64..86_400,
+            nanoseconds in 0i64..1_000_000_000,
+            smooth_hands: bool,
+            hour_follows_minutes: bool,
+            dial_24h: bool,
+        ) {
+            let now = at(0, 0, 0) + Duration::seconds(seconds) + Duration::nanoseconds(nanoseconds);
+            let mut led_clock = LedClock::default();
+            led_clock
+                .set_smooth_hands(smooth_hands)
+                .set_face(ClockFace {
+                    hour_follows_minutes,
+                    dial_24h,
+                    quarter_markers: true,
+                    ..Default::default()
+                })
+                .show_tomorrow(3)
+                .set_tomorrow(Some(LED_LOW_RED));
+            let mut layers = Compositor::new(num_leds, offset % num_leds);
+            led_clock.draw(now, &mut layers);
+
+            // However the hands are split between LEDs each one adds up to its width
+            for (kind, width) in [
+                (LayerKind::HourHand, 2.0),
+                (LayerKind::MinuteHand, 1.0),
+                (LayerKind::SecondHand, 1.0),
+            ]
+            .iter()
+            {
+                let layer = layers.layer(*kind);
+                let covered: f64 = (0..num_leds as isize).map(|led| layer.coverage(led)).sum();
+                prop_assert!((covered - width).abs() < 1e-9, "{:?} covers {}", kind, covered);
+            }
+
+            // Hands that jump from LED to LED land where whole number arithmetic says
+            if !smooth_hands {
+                let dial_hours = if dial_24h { 24 } else { 12 };
+                let mut hour = (now.hour() % dial_hours) as usize * 60;
+                if hour_follows_minutes {
+                    hour += now.minute() as usize;
+                }
+                let hour = hour * num_leds / (dial_hours as usize * 60);
+                let minute = now.minute() as usize * num_leds / 60;
+                let second = now.second() as usize * num_leds / 60;
+                prop_assert_eq!(layers.layer(LayerKind::HourHand).coverage(hour as isize), 1.0);
+                prop_assert_eq!(layers.layer(LayerKind::MinuteHand).coverage(minute as isize), 1.0);
+                prop_assert_eq!(layers.layer(LayerKind::SecondHand).coverage(second as isize), 1.0);
+            }
+        }
     }
 }
@@ -0,0 +1,213 @@
+use crate::error::FlowerError;
+use crate::led::LedValue;
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+/// Colours from the config are at the same low brightness as the built in ones
+const CONFIG_BRIGHTNESS: u8 = 1;
+
+/// sRGB channel values to the linear values the LEDs' PWM needs to look the same
+fn srgb_to_linear_table() -> &'static [u8; 256] {
+    static TABLE: OnceLock<[u8; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (srgb, linear) in table.iter_mut().enumerate() {
+            let c = srgb as f64 / 255.0;
+            let l = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            *linear = (l * 255.0).round() as u8;
+        }
+        table
+    })
+}
+
+pub fn srgb_to_linear(channel: u8) -> u8 {
+    srgb_to_linear_table()[channel as usize]
+}
+
+/// How much of each channel to use, from 0.0 to 1.0, to make white look white on a
+/// particular batch of LEDs
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhiteBalance {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        WhiteBalance {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        }
+    }
+}
+
+/// Turns the colours the clock asks for into what the hardware needs to show them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ColourCorrection {
+    pub gamma: bool,
+    pub white_balance: WhiteBalance,
+}
+
+impl ColourCorrection {
+    pub fn apply(&self, value: &LedValue) -> LedValue {
+        let correct = |channel: u8, balance: f64| {
+            let channel = if self.gamma {
+                srgb_to_linear(channel)
+            } else {
+                channel
+            };
+            (channel as f64 * balance.clamp(0.0, 1.0)).round() as u8
+        };
+        value.with_channels(
+            correct(value.red(), self.white_balance.red),
+            correct(value.green(), self.white_balance.green),
+            correct(value.blue(), self.white_balance.blue),
+        )
+    }
+}
+
+/// A colour written as "#8000ff" or "hsl(270, 100%, 50%)" in the config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Colour(pub LedValue);
+
+impl TryFrom<String> for Colour {
+    type Error = FlowerError;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        parse_colour(value.trim()).map(Colour).ok_or_else(|| {
+            FlowerError::ConfigError(format!(
+                "{} is not a colour like #8000ff or hsl(270, 100%, 50%)",
+                value
+            ))
+        })
+    }
+}
+
+fn parse_colour(value: &str) -> Option<LedValue> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+        return LedValue::new(CONFIG_BRIGHTNESS, channel(0)?, channel(2)?, channel(4)?).ok();
+    }
+    let hsl = value.strip_prefix("hsl(")?.strip_suffix(')')?;
+    let parts: Vec<&str> = hsl.split(',').map(str::trim).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let percent = |part: &str| Some(part.strip_suffix('%')?.trim().parse::<f64>().ok()? / 100.0);
+    LedValue::from_hsl(
+        CONFIG_BRIGHTNESS,
+        parts[0].parse().ok()?,
+        percent(parts[1])?,
+        percent(parts[2])?,
+    )
+    .ok()
+}
+
+/// Red, green and blue for a hue in degrees and a chroma from 0.0 to 1.0, with `m` added to
+/// each channel to set how light the colour is
+pub fn hue_to_rgb(hue: f64, chroma: f64, m: f64) -> (u8, u8, u8) {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let to_byte = |channel: f64| ((channel + m).clamp(0.0, 1.0) * 255.0).round() as u8;
+    (to_byte(r), to_byte(g), to_byte(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_darkens_the_middle_but_not_the_ends() {
+        assert_eq!(srgb_to_linear(0), 0);
+        assert_eq!(srgb_to_linear(255), 255);
+        assert_eq!(srgb_to_linear(128), 55);
+        let table = srgb_to_linear_table();
+        assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn correction_applies_gamma_then_white_balance() {
+        let correction = ColourCorrection {
+            gamma: true,
+            white_balance: WhiteBalance {
+                red: 1.0,
+                green: 0.5,
+                blue: 1.0,
+            },
+        };
+        let corrected = correction.apply(&LedValue::new(3, 255, 255, 128).unwrap());
+        assert_eq!(corrected, LedValue::new(3, 255, 128, 55).unwrap());
+
+        let uncorrected = ColourCorrection::default();
+        let value = LedValue::new(3, 10, 20, 30).unwrap();
+        assert_eq!(uncorrected.apply(&value), value);
+    }
+
+    #[test]
+    fn hsv_and_hsl_give_the_expected_colours() {
+        let hsv = |hue, saturation, value| {
+            let led = LedValue::from_hsv(1, hue, saturation, value).unwrap();
+            (led.red(), led.green(), led.blue())
+        };
+        assert_eq!(hsv(0.0, 1.0, 1.0), (255, 0, 0));
+        assert_eq!(hsv(60.0, 1.0, 1.0), (255, 255, 0));
+        assert_eq!(hsv(120.0, 1.0, 1.0), (0, 255, 0));
+        assert_eq!(hsv(240.0, 1.0, 0.5), (0, 0, 128));
+        assert_eq!(hsv(-60.0, 1.0, 1.0), (255, 0, 255));
+        assert_eq!(hsv(200.0, 0.0, 1.0), (255, 255, 255));
+
+        let hsl = |hue, saturation, lightness| {
+            let led = LedValue::from_hsl(1, hue, saturation, lightness).unwrap();
+            (led.red(), led.green(), led.blue())
+        };
+        assert_eq!(hsl(0.0, 1.0, 0.5), (255, 0, 0));
+        assert_eq!(hsl(180.0, 1.0, 0.5), (0, 255, 255));
+        assert_eq!(hsl(120.0, 1.0, 0.75), (128, 255, 128));
+        assert_eq!(hsl(0.0, 0.0, 0.0), (0, 0, 0));
+        assert!(LedValue::from_hsl(32, 0.0, 1.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn colours_are_read_as_hex_or_hsl() {
+        let colour = |value: &str| Colour::try_from(value.to_string()).map(|colour| colour.0);
+        assert_eq!(
+            colour("#8000ff").unwrap(),
+            LedValue::new(1, 128, 0, 255).unwrap()
+        );
+        assert_eq!(
+            colour(" hsl(180, 100%, 50%) ").unwrap(),
+            LedValue::new(1, 0, 255, 255).unwrap()
+        );
+        for bad in [
+            "purple",
+            "#80f",
+            "#80z0ff",
+            "hsl(180, 1, 0.5)",
+            "hsl(180, 100%)",
+        ]
+        .iter()
+        {
+            assert!(colour(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::led::{BlendMode, LedArray, LedValue};
+use serde::Deserialize;
+
+/// The layers the ring is built up from, lowest first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerKind {
+    /// Whatever the display mode shows, such as the pollen colour
+    Background,
+    HourHand,
+    MinuteHand,
+    SecondHand,
+    /// Something that needs fixing, shown until it is
+    Alerts,
+    /// Brief acknowledgements, such as a reloaded config
+    Notifications,
+}
+
+const ALL_LAYERS: [LayerKind; 6] = [
+    LayerKind::Background,
+    LayerKind::HourHand,
+    LayerKind::MinuteHand,
+    LayerKind::SecondHand,
+    LayerKind::Alerts,
+    LayerKind::Notifications,
+];
+
+impl LayerKind {
+    /// Higher layers are drawn over lower ones. Layers at the same height share the LEDs they
+    /// overlap on rather than one covering the other.
+    fn z(self) -> i32 {
+        match self {
+            Self::Background => 0,
+            Self::HourHand | Self::MinuteHand | Self::SecondHand => 10,
+            Self::Alerts => 20,
+            Self::Notifications => 30,
+        }
+    }
+}
+
+/// How a layer is drawn onto the layers below
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayerStyle {
+    /// From 0.0 for invisible to 1.0
+    pub opacity: f64,
+    pub blend: BlendMode,
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        LayerStyle {
+            opacity: 1.0,
+            blend: BlendMode::Over,
+        }
+    }
+}
+
+struct Layer {
+    kind: LayerKind,
+    style: LayerStyle,
+    frame: LedArray,
+}
+
+/// A stack of layers, each drawn separately then combined into one frame
+pub struct Compositor {
+    layers: Vec<Layer>,
+}
+
+impl Compositor {
+    pub fn new(size: usize, offset: usize) -> Compositor {
+        let mut layers: Vec<Layer> = ALL_LAYERS
+            .iter()
+            .map(|kind| Layer {
+                kind: *kind,
+                style: LayerStyle::default(),
+                frame: LedArray::transparent(size, offset),
+            })
+            .collect();
+        layers.sort_by_key(|layer| layer.kind.z());
+        Compositor { layers }
+    }
+
+    pub fn set_style(&mut self, kind: LayerKind, style: LayerStyle) -> &mut Self {
+        self.find(kind).style = style;
+        self
+    }
+
+    fn find(&mut self, kind: LayerKind) -> &mut Layer {
+        self.layers
+            .iter_mut()
+            .find(|layer| layer.kind == kind)
+            .expect("every kind of layer is created up front")
+    }
+
+    pub fn layer(&mut self, kind: LayerKind) -> &mut LedArray {
+        &mut self.find(kind).frame
+    }
+
+    /// Makes every layer transparent, ready to draw the next frame
+    pub fn clear(&mut self) -> &mut Self {
+        for layer in self.layers.iter_mut() {
+            layer.frame.clear();
+        }
+        self
+    }
+
+    /// Draws every layer, lowest first, into `into`. Where layers at the same height overlap
+    /// each gets an equal share of the LED, so two hands on one LED show as a mix of both.
+    pub fn composite(&self, into: &mut LedArray) {
+        into.fill(LedValue::default());
+        let num_leds = into.len();
+        let mut shared = vec![0.0; num_leds];
+        let mut z = None;
+        for layer in self.layers.iter() {
+            if z != Some(layer.kind.z()) {
+                z = Some(layer.kind.z());
+                shared.iter_mut().for_each(|coverage| *coverage = 0.0);
+            }
+            for (position, shared) in shared.iter_mut().enumerate() {
+                let position = position as isize;
+                let amount = layer.frame.coverage(position) * layer.style.opacity.clamp(0.0, 1.0);
+                if amount <= 0.0 {
+                    continue;
+                }
+                let share = amount / (*shared + amount).max(1.0);
+                into.draw(
+                    position,
+                    layer.frame.get(position),
+                    share,
+                    layer.style.blend,
+                );
+                *shared += amount;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::value::{LED_LOW_BLUE, LED_LOW_GREEN, LED_LOW_PURPLE, LED_RED};
+
+    #[test]
+    fn higher_layers_cover_lower_ones() {
+        let mut compositor = Compositor::new(4, 0);
+        compositor.layer(LayerKind::Background).fill(LED_LOW_GREEN);
+        compositor.layer(LayerKind::Alerts).set(1, LED_RED);
+        compositor
+            .layer(LayerKind::Notifications)
+            .draw(2, LED_LOW_BLUE, 0.5, BlendMode::Over);
+        let mut frame = LedArray::new(4, 0);
+        compositor.composite(&mut frame);
+        assert_eq!(frame.get(0), LED_LOW_GREEN);
+        assert_eq!(frame.get(1), LED_RED);
+        assert_eq!((frame.get(2).green(), frame.get(2).blue()), (128, 128));
+    }
+
+    #[test]
+    fn coincident_hands_are_mixed() {
+        let mut compositor = Compositor::new(4, 0);
+        compositor.layer(LayerKind::Background).fill(LED_LOW_GREEN);
+        compositor.layer(LayerKind::HourHand).set(0, LED_LOW_PURPLE);
+        compositor.layer(LayerKind::MinuteHand).set(0, LED_LOW_BLUE);
+        compositor.layer(LayerKind::MinuteHand).set(1, LED_LOW_BLUE);
+        let mut frame = LedArray::new(4, 0);
+        compositor.composite(&mut frame);
+        let mixed = frame.get(0);
+        assert_eq!((mixed.red(), mixed.green(), mixed.blue()), (128, 0, 255));
+        assert_eq!(frame.get(1), LED_LOW_BLUE);
+
+        // Opacity and blend modes come from the style
+        compositor.set_style(
+            LayerKind::MinuteHand,
+            LayerStyle {
+                opacity: 0.5,
+                blend: BlendMode::Multiply,
+            },
+        );
+        compositor.composite(&mut frame);
+        let tinted = frame.get(1);
+        assert_eq!((tinted.green(), tinted.blue()), (128, 0));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::led::LedValue;
+use crate::pollen::PollenCount;
+use std::f64::consts::PI;
+
+/// How a colour changes over time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LedEffect {
+    #[default]
+    Steady,
+    /// Fades the colour down to `floor` of its full value and back again every `period_ms`
+    Pulse { period_ms: u64, floor: f64 },
+}
+
+impl LedEffect {
+    /// The colour `elapsed_ms` into the effect
+    pub fn apply(self, value: LedValue, elapsed_ms: u64) -> LedValue {
+        match self {
+            Self::Steady => value,
+            Self::Pulse { period_ms, floor } => {
+                let phase = (elapsed_ms % period_ms) as f64 / period_ms as f64;
+                // Starts at full brightness, dips to the floor half way through the period
+                let wave = (1.0 + (2.0 * PI * phase).cos()) / 2.0;
+                value.scaled(floor + (1.0 - floor) * wave)
+            }
+        }
+    }
+}
+
+/// A quick throb, very high pollen should be noticed
+pub const POLLEN_VERY_HIGH_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 1_500,
+    floor: 0.2,
+};
+
+/// A slow breath, there is no pollen count out of season but the flower is still working
+pub const POLLEN_NOT_AVAILABLE_EFFECT: LedEffect = LedEffect::Pulse {
+    period_ms: 8_000,
+    floor: 0.3,
+};
+
+impl From<Option<PollenCount>> for LedEffect {
+    fn from(count: Option<PollenCount>) -> Self {
+        match count {
+            Some(PollenCount::VeryHigh) => POLLEN_VERY_HIGH_EFFECT,
+            Some(PollenCount::NotAvailable) => POLLEN_NOT_AVAILABLE_EFFECT,
+            _ => LedEffect::Steady,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::value::LED_LOW_RED;
+
+    #[test]
+    fn steady_never_changes() {
+        assert_eq!(LedEffect::Steady.apply(LED_LOW_RED, 12_345), LED_LOW_RED);
+    }
+
+    #[test]
+    fn pulse_dips_to_the_floor_half_way_through() {
+        let pulse = LedEffect::Pulse {
+            period_ms: 1_000,
+            floor: 0.2,
+        };
+        assert_eq!(pulse.apply(LED_LOW_RED, 0), LED_LOW_RED);
+        assert_eq!(pulse.apply(LED_LOW_RED, 500).red(), 51);
+        assert_eq!(pulse.apply(LED_LOW_RED, 1_000), LED_LOW_RED);
+    }
+}
@@ -1,13 +1,31 @@
 use crate::error::{FlowerError, Result};
-use crate::led::{LedMessage, LedValue};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use crate::led::backend::LedBackend;
+use crate::led::LedValue;
+use serde::Deserialize;
 
-const NULL_MESSAGE: LedMessage = [0, 0, 0, 0];
+/// What LEDs that weren't written to since the last flush show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FillPolicy {
+    #[default]
+    Off,
+    /// Keep showing whatever they showed in the last frame
+    Hold,
+}
 
+/// Collects writes into a frame of exactly `size` LEDs. Every flush sends a whole frame, the
+/// LEDs that weren't written are filled according to the fill policy.
 pub struct LedInterface {
     size: usize,
     back_buffer: Vec<LedValue>,
-    spi: Spi,
+    /// How many LEDs of the back buffer have been written since the last flush
+    written: usize,
+    front_buffer: Vec<LedValue>,
+    fill: FillPolicy,
+    /// How much of their light the LEDs give, from 0.0 to 1.0, passed to the backend so it
+    /// can dim after correcting the colours
+    brightness: f64,
+    backend: Box<dyn LedBackend>,
 }
 
 pub trait LedWritable {
@@ -15,23 +33,36 @@ pub trait LedWritable {
 }
 
 impl LedInterface {
-    pub fn new(size: usize) -> Result<LedInterface> {
-        let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss1, 30_000_000, Mode::Mode0)?;
-        // self.spi.write(&NULL_MESSAGE)?;
-        let back_buffer = vec![];
+    pub fn new(size: usize, backend: Box<dyn LedBackend>) -> Result<LedInterface> {
         let mut led_array = LedInterface {
-            back_buffer,
-            spi,
             size,
+            back_buffer: vec![LedValue::default(); size],
+            written: 0,
+            front_buffer: vec![LedValue::default(); size],
+            fill: FillPolicy::default(),
+            brightness: 1.0,
+            backend,
         };
         led_array.flush()?;
         Ok(led_array)
     }
 
+    pub fn set_fill(&mut self, fill: FillPolicy) -> &mut Self {
+        self.fill = fill;
+        self
+    }
+
+    pub fn set_brightness(&mut self, brightness: f64) -> &mut Self {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self
+    }
+
     pub fn write<W: LedWritable>(&mut self, writable: &W) -> Result<&mut Self> {
         let slice = writable.as_array();
-        if self.back_buffer.len() + slice.len() <= self.size {
-            self.back_buffer.extend_from_slice(slice);
+        let end = self.written + slice.len();
+        if end <= self.size {
+            self.back_buffer[self.written..end].copy_from_slice(slice);
+            self.written = end;
             Ok(self)
         } else {
             Err(FlowerError::SimpleError(
@@ -41,18 +72,26 @@ impl LedInterface {
         }
     }
 
+    /// Turns every LED off in the next frame
     pub fn clear(&mut self) -> &mut Self {
-        self.back_buffer = vec![LedValue::default(); self.size];
+        self.back_buffer.fill(LedValue::default());
+        self.written = self.size;
         self
     }
 
     pub fn flush(&mut self) -> Result<&mut Self> {
-        // Drain the back buffer into the spi interface
-        for led_value in self.back_buffer.drain(..).into_iter() {
-            self.spi.write(&led_value.as_array())?;
+        let unwritten = self.written..self.size;
+        match self.fill {
+            FillPolicy::Off => self.back_buffer[unwritten].fill(LedValue::default()),
+            FillPolicy::Hold => {
+                self.back_buffer[unwritten.clone()].copy_from_slice(&self.front_buffer[unwritten])
+            }
         }
-        // Send a null message to finish the message
-        self.spi.write(&NULL_MESSAGE)?;
+        self.backend
+            .write_frame(&self.back_buffer, self.brightness)?;
+        // The next frame starts unwritten
+        self.front_buffer.copy_from_slice(&self.back_buffer);
+        self.written = 0;
         Ok(self)
     }
 }
@@ -62,3 +101,115 @@ impl Drop for LedInterface {
         let _ = self.clear().flush();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LedConfig;
+    use crate::led::backend::{CapturingTransport, RecordingBackend, SpiBackend};
+    use crate::led::value::{LED_LOW_GREEN, LED_LOW_RED};
+
+    struct Pixels(Vec<LedValue>);
+
+    impl LedWritable for Pixels {
+        fn as_array(&self) -> &[LedValue] {
+            self.0.as_slice()
+        }
+    }
+
+    #[test]
+    fn flush_hands_the_back_buffer_to_the_backend() {
+        let recording = RecordingBackend::new();
+        let mut interface = LedInterface::new(3, Box::new(recording.clone())).unwrap();
+        interface
+            .write(&Pixels(vec![LED_LOW_RED; 3]))
+            .unwrap()
+            .flush()
+            .unwrap();
+        assert_eq!(recording.last_frame(), Some(vec![LED_LOW_RED; 3]));
+        interface.flush().unwrap();
+        assert_eq!(recording.frames().len(), 3);
+        assert_eq!(recording.last_frame(), Some(vec![LedValue::default(); 3]));
+    }
+
+    #[test]
+    fn unwritten_leds_are_filled_by_policy() {
+        let recording = RecordingBackend::new();
+        let mut interface = LedInterface::new(3, Box::new(recording.clone())).unwrap();
+        interface.write(&Pixels(vec![LED_LOW_RED; 3])).unwrap();
+        interface.flush().unwrap();
+        interface.write(&Pixels(vec![LED_LOW_GREEN])).unwrap();
+        interface.flush().unwrap();
+        let off = LedValue::default();
+        assert_eq!(recording.last_frame(), Some(vec![LED_LOW_GREEN, off, off]));
+
+        interface.set_fill(FillPolicy::Hold);
+        interface.write(&Pixels(vec![LED_LOW_RED])).unwrap();
+        interface.flush().unwrap();
+        assert_eq!(recording.last_frame(), Some(vec![LED_LOW_RED, off, off]));
+        interface.flush().unwrap();
+        assert_eq!(recording.last_frame(), Some(vec![LED_LOW_RED, off, off]));
+    }
+
+    #[test]
+    fn brightness_dims_the_whole_frame() {
+        let recording = RecordingBackend::new();
+        let mut interface = LedInterface::new(2, Box::new(recording.clone())).unwrap();
+        interface.set_brightness(0.5);
+        interface.write(&Pixels(vec![LED_LOW_RED; 2])).unwrap();
+        interface.flush().unwrap();
+        assert_eq!(
+            recording.last_frame(),
+            Some(vec![LED_LOW_RED.dimmed(0.5); 2])
+        );
+
+        // Held LEDs keep their own colour, not the dimmed one
+        interface.set_fill(FillPolicy::Hold).set_brightness(1.0);
+        interface.flush().unwrap();
+        assert_eq!(recording.last_frame(), Some(vec![LED_LOW_RED; 2]));
+    }
+
+    #[test]
+    fn every_flush_is_a_whole_frame_on_the_wire() {
+        let capturing = CapturingTransport::default();
+        let leds = LedConfig {
+            count: 3,
+            ..Default::default()
+        };
+        let backend = SpiBackend::with_transport(Box::new(capturing.clone()), &leds);
+        let mut interface = LedInterface::new(3, Box::new(backend)).unwrap();
+        interface.write(&Pixels(vec![LED_LOW_RED])).unwrap();
+        interface.flush().unwrap();
+
+        // Start frame, the red LED, two unwritten LEDs at zero brightness, end frame
+        #[rustfmt::skip]
+        let expected = vec![
+            0, 0, 0, 0,
+            0xE1, 0, 0, 255,
+            0xE0, 255, 255, 255,
+            0xE0, 255, 255, 255,
+            0, 0, 0, 0,
+        ];
+        assert_eq!(capturing.last_transfer(), Some(expected));
+
+        drop(interface);
+        let cleared = capturing.last_transfer().unwrap();
+        assert_eq!(cleared.len(), 20);
+        assert_eq!(&cleared[4..8], &[0xE0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn write_refuses_to_overflow_the_back_buffer() {
+        let recording = RecordingBackend::new();
+        let mut interface = LedInterface::new(2, Box::new(recording)).unwrap();
+        assert!(interface.write(&Pixels(vec![LED_LOW_RED; 3])).is_err());
+    }
+
+    #[test]
+    fn dropping_the_interface_clears_the_leds() {
+        let recording = RecordingBackend::new();
+        let interface = LedInterface::new(2, Box::new(recording.clone())).unwrap();
+        drop(interface);
+        assert_eq!(recording.last_frame(), Some(vec![LedValue::default(); 2]));
+    }
+}
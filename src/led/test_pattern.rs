@@ -0,0 +1,287 @@
+use crate::error::{FlowerError, Result};
+use crate::led::value::{LED_TEST_BLUE, LED_TEST_GREEN, LED_TEST_RED, LED_TEST_WHITE};
+use crate::led::{LedInterface, LedValue, LedWritable};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// One step of a test pattern, how long to show it and what to say about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestFrame {
+    pixels: Vec<LedValue>,
+    hold: Duration,
+    label: Option<String>,
+}
+
+impl TestFrame {
+    fn new(pixels: Vec<LedValue>, hold: Duration) -> TestFrame {
+        TestFrame {
+            pixels,
+            hold,
+            label: None,
+        }
+    }
+
+    fn single(size: usize, index: usize, value: LedValue, hold: Duration) -> TestFrame {
+        let mut pixels = vec![LedValue::default(); size];
+        pixels[index] = value;
+        TestFrame::new(pixels, hold)
+    }
+
+    fn labelled(mut self, label: String) -> TestFrame {
+        self.label = Some(label);
+        self
+    }
+}
+
+impl LedWritable for TestFrame {
+    fn as_array(&self) -> &[LedValue] {
+        &self.pixels
+    }
+}
+
+/// The patterns `flower selftest` can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    /// A white dot steps round the ring, a dark step is a dead pixel
+    EachLed,
+    /// The ring fills with red, then green, then blue. A wrong colour means the channel order
+    /// is wrong.
+    RgbSweep,
+    /// The whole ring in white at every brightness level from 0 to 31
+    BrightnessRamp,
+    /// A colour wheel round the ring. Colours should blend evenly with no LED standing out, if
+    /// not adjust `leds.gamma` and `leds.white_balance`.
+    Rainbow,
+    /// A slow green dot with each index printed as it lights, LED 0 stays red. The index of the
+    /// LED at the top of the ring is `leds.offset`.
+    Chase,
+}
+
+pub const ALL_PATTERNS: [TestPattern; 5] = [
+    TestPattern::EachLed,
+    TestPattern::RgbSweep,
+    TestPattern::BrightnessRamp,
+    TestPattern::Rainbow,
+    TestPattern::Chase,
+];
+
+impl TestPattern {
+    pub const NAMES: [&'static str; 5] = [
+        "each-led",
+        "rgb-sweep",
+        "brightness-ramp",
+        "rainbow",
+        "chase",
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::EachLed => Self::NAMES[0],
+            Self::RgbSweep => Self::NAMES[1],
+            Self::BrightnessRamp => Self::NAMES[2],
+            Self::Rainbow => Self::NAMES[3],
+            Self::Chase => Self::NAMES[4],
+        }
+    }
+
+    /// How long each step is shown unless told otherwise
+    fn default_step(self) -> Duration {
+        match self {
+            Self::EachLed => Duration::from_millis(150),
+            Self::RgbSweep => Duration::from_millis(50),
+            Self::BrightnessRamp => Duration::from_millis(200),
+            Self::Rainbow => Duration::from_millis(50),
+            Self::Chase => Duration::from_millis(1500),
+        }
+    }
+
+    /// The frames for a ring of `size` LEDs, each shown for `step` or the pattern's default
+    pub fn frames(self, size: usize, step: Option<Duration>) -> Result<Vec<TestFrame>> {
+        let step = step.unwrap_or_else(|| self.default_step());
+        let frames = match self {
+            Self::EachLed => (0..size)
+                .map(|index| TestFrame::single(size, index, LED_TEST_WHITE, step))
+                .collect(),
+            Self::RgbSweep => [
+                ("red", LED_TEST_RED),
+                ("green", LED_TEST_GREEN),
+                ("blue", LED_TEST_BLUE),
+            ]
+            .iter()
+            .flat_map(|(name, value)| {
+                (1..=size).map(move |lit| {
+                    let mut pixels = vec![*value; lit];
+                    pixels.resize(size, LedValue::default());
+                    let frame = TestFrame::new(pixels, step);
+                    if lit == 1 {
+                        frame.labelled(format!("Sweeping {}", name))
+                    } else {
+                        frame
+                    }
+                })
+            })
+            .collect(),
+            Self::BrightnessRamp => (0..=31)
+                .map(|brightness| {
+                    let value = LedValue::new(brightness, 255, 255, 255)?;
+                    Ok(TestFrame::new(vec![value; size], step)
+                        .labelled(format!("Brightness {}", brightness)))
+                })
+                .collect::<Result<Vec<TestFrame>>>()?,
+            // The wheel turns one step a frame, once round the ring
+            Self::Rainbow => (0..size)
+                .map(|turn| {
+                    let pixels = (0..size)
+                        .map(|index| {
+                            let hue = 360.0 * ((index + turn) % size) as f64 / size as f64;
+                            LedValue::from_hsv(4, hue, 1.0, 1.0)
+                        })
+                        .collect::<Result<Vec<LedValue>>>()?;
+                    Ok(TestFrame::new(pixels, step))
+                })
+                .collect::<Result<Vec<TestFrame>>>()?,
+            Self::Chase => (0..size)
+                .map(|index| {
+                    let mut frame = TestFrame::single(size, index, LED_TEST_GREEN, step);
+                    if index != 0 {
+                        frame.pixels[0] = LED_TEST_RED;
+                    }
+                    frame.labelled(format!("LED {}", index))
+                })
+                .collect(),
+        };
+        Ok(frames)
+    }
+}
+
+impl fmt::Display for TestPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TestPattern {
+    type Err = FlowerError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ALL_PATTERNS
+            .iter()
+            .find(|pattern| pattern.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                FlowerError::ConfigError(format!(
+                    "Unknown test pattern {}, expected one of {}",
+                    s,
+                    TestPattern::NAMES.join(", ")
+                ))
+            })
+    }
+}
+
+/// A short check that every LED lights and every channel works: a white dot runs round the
+/// ring, then the whole ring shows red, green and blue
+pub fn quick_test(size: usize) -> Vec<TestFrame> {
+    let mut frames: Vec<TestFrame> = (0..size)
+        .map(|index| TestFrame::single(size, index, LED_TEST_WHITE, Duration::from_millis(50)))
+        .collect();
+    for value in [LED_TEST_RED, LED_TEST_GREEN, LED_TEST_BLUE].iter() {
+        frames.push(TestFrame::new(
+            vec![*value; size],
+            Duration::from_millis(500),
+        ));
+    }
+    frames
+}
+
+/// Shows each frame in turn, printing any labels, then clears the LEDs. Anything arriving on
+/// `interrupt` stops the pattern early and is given back so it can still be acted on.
+pub fn play<T>(
+    interface: &mut LedInterface,
+    frames: &[TestFrame],
+    interrupt: &Receiver<T>,
+) -> Result<Option<T>> {
+    let mut interrupted = None;
+    for frame in frames {
+        if let Some(label) = &frame.label {
+            println!("{}", label);
+        }
+        interface.write(frame)?.flush()?;
+        match interrupt.recv_timeout(frame.hold) {
+            Ok(message) => {
+                interrupted = Some(message);
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // Nothing can interrupt any more
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(frame.hold),
+        }
+    }
+    interface.clear().flush()?;
+    Ok(interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::RecordingBackend;
+    use crossbeam_channel::{bounded, never};
+
+    #[test]
+    fn every_frame_fills_the_ring() {
+        for pattern in ALL_PATTERNS.iter() {
+            let frames = pattern.frames(24, None).unwrap();
+            assert!(!frames.is_empty(), "{}", pattern);
+            assert!(frames.iter().all(|frame| frame.pixels.len() == 24));
+        }
+    }
+
+    #[test]
+    fn the_brightness_ramp_covers_every_level() {
+        let frames = TestPattern::BrightnessRamp.frames(3, None).unwrap();
+        let levels: Vec<u8> = frames.iter().map(|f| f.pixels[0].brightness()).collect();
+        assert_eq!(levels, (0..=31).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn the_chase_numbers_each_led() {
+        let frames = TestPattern::Chase.frames(12, None).unwrap();
+        assert_eq!(frames[5].label, Some("LED 5".to_string()));
+        assert_eq!(frames[5].pixels[5], LED_TEST_GREEN);
+        assert_eq!(frames[5].pixels[0], LED_TEST_RED);
+    }
+
+    #[test]
+    fn patterns_are_named_on_the_command_line() {
+        for pattern in ALL_PATTERNS.iter() {
+            assert_eq!(
+                pattern.to_string().parse::<TestPattern>().unwrap(),
+                *pattern
+            );
+        }
+        assert!("sparkle".parse::<TestPattern>().is_err());
+    }
+
+    #[test]
+    fn playing_can_be_interrupted() {
+        let recording = RecordingBackend::new();
+        let mut interface = LedInterface::new(4, Box::new(recording.clone())).unwrap();
+        let frames = TestPattern::EachLed
+            .frames(4, Some(Duration::from_millis(1)))
+            .unwrap();
+        assert_eq!(play(&mut interface, &frames, &never::<()>()).unwrap(), None);
+        // The initial flush, one per frame, then the clear
+        assert_eq!(recording.frames().len(), 6);
+
+        let (sender, receiver) = bounded(1);
+        sender.send("stop").unwrap();
+        assert_eq!(
+            play(&mut interface, &frames, &receiver).unwrap(),
+            Some("stop")
+        );
+        assert_eq!(recording.frames().len(), 8);
+        assert_eq!(recording.last_frame(), Some(vec![LedValue::default(); 4]));
+    }
+}
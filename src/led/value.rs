@@ -1,9 +1,9 @@
 use crate::error::FlowerError;
-use crate::led::LedMessage;
+use crate::led::colour::hue_to_rgb;
 use crate::pollen::PollenCount;
 use crate::Result;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LedValue {
     brightness: u8,
     blue: u8,
@@ -25,10 +25,142 @@ impl LedValue {
         }
     }
 
-    pub fn as_array(&self) -> LedMessage {
-        const BRIGHTNESS_MOD: u8 = 224;
-        let brightness = self.brightness + BRIGHTNESS_MOD;
-        [brightness, self.blue, self.green, self.red]
+    /// A colour from a hue in degrees and a saturation and value from 0.0 to 1.0
+    pub fn from_hsv(brightness: u8, hue: f64, saturation: f64, value: f64) -> Result<LedValue> {
+        let chroma = value.clamp(0.0, 1.0) * saturation.clamp(0.0, 1.0);
+        let (red, green, blue) = hue_to_rgb(hue, chroma, value.clamp(0.0, 1.0) - chroma);
+        LedValue::new(brightness, red, green, blue)
+    }
+
+    /// A colour from a hue in degrees and a saturation and lightness from 0.0 to 1.0
+    pub fn from_hsl(brightness: u8, hue: f64, saturation: f64, lightness: f64) -> Result<LedValue> {
+        let lightness = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation.clamp(0.0, 1.0);
+        let (red, green, blue) = hue_to_rgb(hue, chroma, lightness - chroma / 2.0);
+        LedValue::new(brightness, red, green, blue)
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+
+    /// The same brightness with different colour channels
+    pub fn with_channels(&self, red: u8, green: u8, blue: u8) -> LedValue {
+        LedValue {
+            brightness: self.brightness,
+            red,
+            green,
+            blue,
+        }
+    }
+
+    /// The same colour with each channel multiplied by `factor`, from 0.0 to 1.0
+    pub fn scaled(&self, factor: f64) -> LedValue {
+        let factor = factor.clamp(0.0, 1.0);
+        let scale = |channel: u8| (channel as f64 * factor).round() as u8;
+        LedValue {
+            brightness: self.brightness,
+            red: scale(self.red),
+            green: scale(self.green),
+            blue: scale(self.blue),
+        }
+    }
+
+    /// The same colour giving `level` of its light, from 0.0 to 1.0. The 0-31 brightness takes
+    /// the coarse steps so the channels keep their resolution, and the channels take the rest.
+    /// The light only goes in proportion once the channels are the linear values the LEDs
+    /// are sent, after any gamma correction. A lit channel stays lit so dim colours keep their
+    /// hue.
+    pub fn dimmed(&self, level: f64) -> LedValue {
+        let level = level.clamp(0.0, 1.0);
+        if self.brightness == 0 || level <= 0.0 {
+            return LedValue {
+                brightness: 0,
+                ..*self
+            };
+        }
+        let target = self.brightness as f64 * level;
+        let brightness = target.ceil().clamp(1.0, 31.0);
+        let factor = target / brightness;
+        let scale = |channel: u8| match channel {
+            0 => 0,
+            _ => ((channel as f64 * factor).round() as u8).max(1),
+        };
+        LedValue {
+            brightness: brightness as u8,
+            red: scale(self.red),
+            green: scale(self.green),
+            blue: scale(self.blue),
+        }
+    }
+
+    /// `channel` as it would be at `brightness`, which is at least this value's brightness
+    fn level_at(&self, channel: u8, brightness: u8) -> f64 {
+        channel as f64 * self.brightness as f64 / brightness as f64
+    }
+
+    /// Combines each channel with `other`'s at the brighter of the two brightness levels
+    fn combine(&self, other: &LedValue, mix: impl Fn(f64, f64) -> f64) -> LedValue {
+        let brightness = self.brightness.max(other.brightness);
+        if brightness == 0 {
+            return *other;
+        }
+        let channel = |mine: u8, theirs: u8| {
+            mix(
+                self.level_at(mine, brightness),
+                other.level_at(theirs, brightness),
+            )
+            .round()
+            .clamp(0.0, 255.0) as u8
+        };
+        LedValue {
+            brightness,
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+        }
+    }
+
+    /// `amount` of the way from this colour to `other`, from 0.0 to 1.0. The channels are
+    /// mixed at the brighter of the two brightness levels so fading in from an LED that is off
+    /// doesn't jump.
+    pub fn blend(&self, other: &LedValue, amount: f64) -> LedValue {
+        let amount = amount.clamp(0.0, 1.0);
+        if amount == 0.0 {
+            return *self;
+        } else if amount == 1.0 || self.brightness.max(other.brightness) == 0 {
+            return *other;
+        }
+        self.combine(other, |mine, theirs| {
+            mine * (1.0 - amount) + theirs * amount
+        })
+    }
+
+    /// Both colours' light together, as if from two LEDs side by side
+    pub fn add(&self, other: &LedValue) -> LedValue {
+        self.combine(other, |mine, theirs| mine + theirs)
+    }
+
+    /// This colour seen through `other`, white leaves it alone and black turns it off
+    pub fn multiply(&self, other: &LedValue) -> LedValue {
+        let filter = |channel: u8| channel as f64 / 255.0;
+        self.with_channels(
+            (self.red as f64 * filter(other.red)).round() as u8,
+            (self.green as f64 * filter(other.green)).round() as u8,
+            (self.blue as f64 * filter(other.blue)).round() as u8,
+        )
     }
 }
 
@@ -43,6 +175,13 @@ impl Default for LedValue {
     }
 }
 
+/// Colours are given as they should look, gamma correction turns them into LED output
+pub const LED_RED: LedValue = LedValue {
+    brightness: 4,
+    red: 255,
+    green: 0,
+    blue: 0,
+};
 pub const LED_LOW_RED: LedValue = LedValue {
     brightness: 1,
     red: 255,
@@ -52,7 +191,7 @@ pub const LED_LOW_RED: LedValue = LedValue {
 pub const LED_LOW_YELLOW: LedValue = LedValue {
     brightness: 1,
     red: 255,
-    green: 150,
+    green: 220,
     blue: 0,
 };
 pub const LED_LOW_GREEN: LedValue = LedValue {
@@ -62,6 +201,28 @@ pub const LED_LOW_GREEN: LedValue = LedValue {
     blue: 0,
 };
 
+pub const LED_LOW_ICE: LedValue = LedValue {
+    brightness: 1,
+    red: 182,
+    green: 208,
+    blue: 255,
+};
+
+pub const LED_LOW_WHITE: LedValue = LedValue {
+    brightness: 1,
+    red: 255,
+    green: 255,
+    blue: 255,
+};
+
+/// Candle light, for a glow with nothing to show
+pub const LED_LOW_WARM: LedValue = LedValue {
+    brightness: 1,
+    red: 255,
+    green: 196,
+    blue: 110,
+};
+
 pub const LED_LOW_BLUE: LedValue = LedValue {
     brightness: 1,
     red: 0,
@@ -81,12 +242,40 @@ pub const LED_LOW_AQUA: LedValue = LedValue {
     blue: 255,
 };
 
+/// Bright enough to judge each channel by eye without drawing too much current
+pub const LED_TEST_WHITE: LedValue = LedValue {
+    brightness: 4,
+    red: 255,
+    green: 255,
+    blue: 255,
+};
+pub const LED_TEST_RED: LedValue = LedValue {
+    brightness: 4,
+    red: 255,
+    green: 0,
+    blue: 0,
+};
+pub const LED_TEST_GREEN: LedValue = LedValue {
+    brightness: 4,
+    red: 0,
+    green: 255,
+    blue: 0,
+};
+pub const LED_TEST_BLUE: LedValue = LedValue {
+    brightness: 4,
+    red: 0,
+    green: 0,
+    blue: 255,
+};
+
 impl From<Option<PollenCount>> for LedValue {
     fn from(count: Option<PollenCount>) -> Self {
         match count {
+            Some(PollenCount::VeryHigh) => LED_RED,
             Some(PollenCount::High) => LED_LOW_RED,
             Some(PollenCount::Medium) => LED_LOW_YELLOW,
             Some(PollenCount::Low) => LED_LOW_GREEN,
+            Some(PollenCount::NotAvailable) => LED_LOW_ICE,
             None => LedValue::default(),
         }
     }
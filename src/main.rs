@@ -1,53 +1,381 @@
+mod brightness;
+mod cli;
 mod clock;
+mod config;
+mod display;
 mod error;
 mod led;
+mod met_api;
 mod pir;
 mod pollen;
+mod retry;
+mod selftest;
 mod signal;
+mod supervisor;
 
+use crate::brightness::Dimmer;
+use crate::cli::{Cli, Command, WeatherSitesOptions};
 use crate::clock::Clock;
-use crate::error::{ErrorHandler, Result};
-use crate::led::{LedClock, LedInterface};
+use crate::config::{Config, WeatherConfig};
+use crate::display::{Display, PollenShown};
+use crate::error::{ErrorHandler, FlowerError, Result};
+use crate::led::test_pattern;
+use crate::led::{LedInterface, SpiBackend, TerminalBackend};
+use crate::met_api::{MetApi, SaneForecast};
 use crate::pir::PassiveInfraRedSensor;
-use crate::pollen::{get_pollen_count, PollenCount};
-use crate::signal::Signal;
-use crossbeam_channel::{after, bounded, never, select, tick, Sender};
+use crate::pollen::{
+    CachedForecast, FallbackProvider, PollenCache, PollenForecast, PollenProvider,
+};
+use crate::retry::{retry, RetryPolicy};
+use crate::signal::{Signal, SignalRequest};
+use crate::supervisor::{Supervisor, WorkerFailure, WorkerStop};
+use chrono::{Local, Utc};
+use crossbeam_channel::{after, bounded, never, select, tick, Receiver, Sender};
+use std::process;
+use std::sync::Arc;
 use std::time::Duration;
-use std::{env, thread};
+use structopt::StructOpt;
+
+/// How long background threads get to finish when shutting down
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 fn main() {
-    App::new().unwrap().run();
+    let cli = Cli::from_args();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if let Some(Command::Selftest(options)) = &cli.command {
+        if let Err(error) = selftest::run(&cli, &config, options) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::WeatherSites(options)) = &cli.command {
+        if let Err(error) = list_weather_sites(&config, options) {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
+    let app = if cli.simulate {
+        App::simulator(cli, config)
+    } else {
+        App::new(cli, config)
+    };
+    match app {
+        Ok(mut app) => app.run(),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Prints the DataPoint sites matching the search, to find the one to use for weather.site_id
+fn list_weather_sites(config: &Config, options: &WeatherSitesOptions) -> Result<()> {
+    let api_key = config.weather.api_key.clone().ok_or_else(|| {
+        FlowerError::ConfigError("weather.api_key or MET_API_KEY must be set".to_string())
+    })?;
+    let search = options.search.as_deref().unwrap_or("").to_lowercase();
+    for site in MetApi::new(api_key).forecast_site_list()? {
+        let area = site
+            .unitary_auth_area
+            .as_deref()
+            .or(site.region.as_deref())
+            .unwrap_or("");
+        if site.name.to_lowercase().contains(&search) || area.to_lowercase().contains(&search) {
+            println!("{:>7}  {} ({})", site.id, site.name, area);
+        }
+    }
+    Ok(())
 }
 
 struct App {
+    /// Kept so the config can be reloaded with the same overrides
+    cli: Cli,
+    config: Config,
     interface: LedInterface,
-    led_clock: LedClock,
+    display: Display,
+    dimmer: Dimmer,
     error_handler: ErrorHandler,
+    /// The last forecast that was fetched, kept when later fetches fail
+    pollen: Option<CachedForecast>,
+    pollen_cache: Option<PollenCache>,
+    /// The last weather forecast that was fetched
+    weather: Option<SaneForecast>,
 }
 
 impl App {
-    pub fn new() -> Result<App> {
-        let error_handler = ErrorHandler::new(&env::var("IFTTT_KEY").unwrap());
-        let clock = Clock::new();
-        let led_clock = LedClock::new(24, 12, clock);
-        match LedInterface::new(24) {
+    pub fn new(cli: Cli, config: Config) -> Result<App> {
+        let error_handler = ErrorHandler::new(config.ifttt_key.as_deref());
+        match SpiBackend::new(&config.spi, &config.leds)
+            .and_then(|spi| LedInterface::new(config.leds.count, Box::new(spi)))
+        {
             Err(error) => {
                 error_handler.handle_error(&error);
-                panic!("{:?}", error);
+                Err(error)
             }
-            Ok(interface) => Ok(App {
-                interface,
-                led_clock,
-                error_handler,
-            }),
+            Ok(interface) => Ok(App::with_interface(interface, error_handler, cli, config)),
         }
     }
 
-    fn update_pollen_count(sender: Sender<Option<PollenCount>>) {
-        // Warning: This process is immediately orphaned
-        thread::spawn(move || {
-            let _ = sender.send(get_pollen_count().ok());
+    /// Draws the ring in the terminal instead of on the LEDs. There is no motion sensor to
+    /// wake the display so it renders constantly, and no light sensor.
+    pub fn simulator(cli: Cli, mut config: Config) -> Result<App> {
+        config.pir.enabled = false;
+        config.brightness.sensor.enabled = false;
+        let error_handler = ErrorHandler::new(config.ifttt_key.as_deref());
+        let backend = TerminalBackend::new(config.leds.offset)?;
+        let interface = LedInterface::new(config.leds.count, Box::new(backend))?;
+        Ok(App::with_interface(interface, error_handler, cli, config))
+    }
+
+    pub fn with_interface(
+        mut interface: LedInterface,
+        error_handler: ErrorHandler,
+        cli: Cli,
+        config: Config,
+    ) -> App {
+        interface.set_fill(config.leds.fill);
+        let display = Display::new(&config, Clock::local());
+        let dimmer = Dimmer::from_config(&config.brightness).unwrap_or_else(|e| {
+            eprintln!(
+                "Could not open the light sensor, going by the schedule: {}",
+                e
+            );
+            Dimmer::new(config.brightness.clone(), None)
+        });
+        let pollen_cache = config.pollen.cache_path().map(PollenCache::new);
+        let pollen = pollen_cache.as_ref().and_then(|cache| match cache.load() {
+            Ok(cached) => cached,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", cache.path().display(), e);
+                None
+            }
         });
+        App {
+            cli,
+            config,
+            interface,
+            display,
+            dimmer,
+            error_handler,
+            pollen,
+            pollen_cache,
+            weather: None,
+        }
+    }
+
+    /// Re-reads the config file, giving the provider for the new pollen settings. The LEDs, SPI
+    /// and sensors are only set up at start up so changes to them are ignored.
+    fn reload_config(&mut self) -> Result<FallbackProvider> {
+        let mut config = Config::load(&self.cli)?;
+        let provider = FallbackProvider::from_config(&config.pollen)?;
+        if self.cli.simulate {
+            config.pir.enabled = false;
+            config.brightness.sensor.enabled = false;
+        }
+        if config.leds.count != self.config.leds.count
+            || config.leds.chipset != self.config.leds.chipset
+            || config.leds.colour_order != self.config.leds.colour_order
+            || config.spi != self.config.spi
+            || config.pir != self.config.pir
+            || config.brightness.sensor != self.config.brightness.sensor
+        {
+            eprintln!("Changes to the LEDs, SPI and sensors need a restart");
+            config.leds.count = self.config.leds.count;
+            config.leds.chipset = self.config.leds.chipset;
+            config.leds.colour_order = self.config.leds.colour_order;
+            config.spi = self.config.spi.clone();
+            config.pir = self.config.pir.clone();
+            config.brightness.sensor = self.config.brightness.sensor.clone();
+        }
+        self.dimmer.set_config(config.brightness.clone());
+        self.interface.set_fill(config.leds.fill);
+        self.display.reconfigure(&config);
+        self.display.notify();
+        self.pollen_cache = config.pollen.cache_path().map(PollenCache::new);
+        // A forecast for somewhere else, or one there is no longer a key for, isn't shown
+        if config.weather.site() != self.config.weather.site() {
+            self.weather = None;
+        }
+        self.config = config;
+        println!("Reloaded config");
+        Ok(provider)
+    }
+
+    /// What the flower is doing, for the log
+    fn state(&self) -> Vec<String> {
+        let now = Local::now();
+        let mut state = vec![
+            format!("Time: {}", now.format("%Y-%m-%d %H:%M:%S")),
+            format!(
+                "LEDs: {} with the top at {}",
+                self.config.leds.count, self.config.leds.offset
+            ),
+            format!("Mode: {}", self.display.active()),
+        ];
+        match &self.pollen {
+            Some(cached) => {
+                let stale =
+                    cached.is_stale(now.with_timezone(&Utc), self.config.pollen.stale_after());
+                state.push(format!(
+                    "Pollen forecast fetched at {}{}",
+                    cached
+                        .fetched_at
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    if stale { " (stale)" } else { "" }
+                ));
+                for (date, count) in cached.forecast.iter() {
+                    state.push(format!("  {}: {}", date, count));
+                }
+            }
+            None => state.push("Pollen forecast: none yet".to_string()),
+        }
+        match (&self.weather, self.config.weather.site()) {
+            (Some(weather), _) => state.push(format!(
+                "Weather forecast for {} ({}) issued at {}",
+                weather.location.name,
+                weather.location.id,
+                weather
+                    .issued_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+            )),
+            (None, Some(_)) => state.push("Weather forecast: none yet".to_string()),
+            (None, None) => {}
+        }
+        state
+    }
+
+    fn log_state(&self, supervisor: &Supervisor, provider: &FallbackProvider, should_render: bool) {
+        for line in self.state() {
+            println!("{}", line);
+        }
+        println!("Display: {}", if should_render { "on" } else { "off" });
+        println!(
+            "Pollen provider: {}",
+            provider
+                .answered_by()
+                .unwrap_or_else(|| "none yet".to_string())
+        );
+        println!("Workers: {}", supervisor.running().join(", "));
+    }
+
+    /// Fetches the forecast in the background, sent along with `generation` so a fetch from
+    /// before a reload can be told apart. A failed fetch sends None and is reported when the
+    /// worker is reaped. The fetch stops when the returned stop is dropped.
+    fn update_pollen_count(
+        supervisor: &mut Supervisor,
+        sender: Sender<(u64, Option<PollenForecast>)>,
+        provider: Arc<FallbackProvider>,
+        retry_policy: RetryPolicy,
+        generation: u64,
+    ) -> Result<WorkerStop> {
+        supervisor.spawn_stoppable("pollen", move |stop| {
+            match retry(&retry_policy, &stop, || provider.forecast()) {
+                Ok(forecast) => {
+                    if let Some(name) = provider.answered_by() {
+                        println!("Pollen forecast from {}", name);
+                    }
+                    let _ = sender.send((generation, Some(forecast)));
+                    Ok(())
+                }
+                Err(_) if stop.is_stopped() => Ok(()),
+                Err(e) => {
+                    let _ = sender.send((generation, None));
+                    Err(e)
+                }
+            }
+        })
+    }
+
+    /// Fetches the weather forecast in the background like the pollen forecast, if there is
+    /// a site to fetch it for
+    fn update_weather(
+        supervisor: &mut Supervisor,
+        sender: Sender<(u64, SaneForecast)>,
+        config: &WeatherConfig,
+        generation: u64,
+    ) -> Result<Option<WorkerStop>> {
+        let (api_key, site_id) = match config.site() {
+            Some(site) => site,
+            None => return Ok(None),
+        };
+        let retry_policy = config.retry.clone();
+        let stop = supervisor.spawn_stoppable("weather", move |stop| {
+            let api = MetApi::new(api_key);
+            match retry(&retry_policy, &stop, || api.forecast(site_id)) {
+                Ok(forecast) => {
+                    let _ = sender.send((generation, forecast));
+                    Ok(())
+                }
+                Err(_) if stop.is_stopped() => Ok(()),
+                Err(e) => Err(e),
+            }
+        })?;
+        Ok(Some(stop))
+    }
+
+    /// A failed fetch is expected now and again so only goes to the log, anything else is
+    /// passed to the error handler
+    fn report_worker_failures(&self, failures: Vec<WorkerFailure>) {
+        for failure in failures {
+            match failure {
+                WorkerFailure::Failed(..) => eprintln!("{}", failure),
+                _ => self
+                    .error_handler
+                    .handle_error(&FlowerError::SimpleError(failure.to_string()).into()),
+            }
+        }
+    }
+
+    fn receive_forecast(&mut self, forecast: Option<PollenForecast>) {
+        // A failed fetch keeps showing the last forecast, it will be dimmed once it's stale
+        if let Some(forecast) = forecast {
+            let cached = CachedForecast::new(forecast, Utc::now());
+            if let Some(cache) = &self.pollen_cache {
+                if let Err(e) = cache.save(&cached) {
+                    eprintln!("Could not write {}: {}", cache.path().display(), e);
+                }
+            }
+            self.pollen = Some(cached);
+        }
+    }
+
+    fn receive_weather(&mut self, forecast: SaneForecast) {
+        // A fetch for a site from before a reload can still finish
+        if self.config.weather.site().map(|(_, site_id)| site_id) != Some(forecast.location.id) {
+            println!(
+                "Ignoring the weather forecast for {} ({}), it isn't weather.site_id",
+                forecast.location.name, forecast.location.id
+            );
+            return;
+        }
+        println!(
+            "Weather forecast for {} issued at {}",
+            forecast.location.name, forecast.issued_at
+        );
+        self.weather = Some(forecast);
+    }
+
+    fn pollen_shown(&self) -> PollenShown {
+        let now = Local::now();
+        let today = now.date().naive_local();
+        let cached = self.pollen.as_ref();
+        PollenShown {
+            today: cached.and_then(|cached| cached.count_on(today)),
+            tomorrow: cached.and_then(|cached| cached.count_on(today.succ())),
+            stale: cached.is_some_and(|cached| {
+                cached.is_stale(now.with_timezone(&Utc), self.config.pollen.stale_after())
+            }),
+        }
     }
 
     pub fn run(&mut self) {
@@ -63,41 +391,154 @@ impl App {
         }
     }
 
+    fn render(&mut self) -> Result<()> {
+        let pollen = self.pollen_shown();
+        self.display.render(pollen, self.weather.as_ref())?;
+        let brightness = self.dimmer.level(Local::now().time());
+        self.interface
+            .set_brightness(brightness)
+            .write(&self.display)?
+            .flush()?;
+        Ok(())
+    }
+
+    /// Runs until asked to shut down, then stops the background threads
     pub fn enter_render_loop(&mut self) -> Result<()> {
-        let (pollen_sender, pollen_receiver) = bounded::<Option<PollenCount>>(1);
-        let sig_receiver = Signal::get_exit_receiver();
-        let render = tick(Duration::from_millis(100));
-        let update_pollen_count = tick(Duration::from_secs(60 * 60));
-        let pir = PassiveInfraRedSensor::new(17)?;
-        let pir_receiver = pir.get_receiver();
-        let mut should_render = false;
+        let mut supervisor = Supervisor::new();
+        let result = self.render_loop(&mut supervisor);
+        self.report_worker_failures(supervisor.shutdown(SHUTDOWN_TIMEOUT));
+        result
+    }
+
+    fn render_loop(&mut self, supervisor: &mut Supervisor) -> Result<()> {
+        let (pollen_sender, pollen_receiver) = bounded::<(u64, Option<PollenForecast>)>(1);
+        let mut provider = Arc::new(FallbackProvider::from_config(&self.config.pollen)?);
+        let sig_receiver = Signal::get_receiver(supervisor)?;
+        let mut render = tick(self.config.leds.frame_interval());
+        let mut update_pollen_count = tick(self.config.pollen.refresh_interval());
+        let (weather_sender, weather_receiver) = bounded::<(u64, SaneForecast)>(1);
+        let mut update_weather = tick(self.config.weather.refresh_interval());
+        let pir_receiver: Receiver<bool> = if self.config.pir.enabled {
+            PassiveInfraRedSensor::new(self.config.pir.pin, supervisor)?.get_receiver()
+        } else {
+            never()
+        };
+        let mut should_render = !self.config.pir.enabled;
         let mut timeout_render = None;
+        // Goes up with each reload, forecasts fetched with older settings are dropped
+        let mut generation = 0;
 
-        App::update_pollen_count(pollen_sender.clone()); // One off run
+        // Kept so that starting a fetch drops, and so stops, the one before it if it is still
+        // going
+        let mut _pollen_fetch = App::update_pollen_count(
+            supervisor,
+            pollen_sender.clone(),
+            provider.clone(),
+            self.config.pollen.retry.clone(),
+            generation,
+        )?;
+        let mut _weather_fetch = App::update_weather(
+            supervisor,
+            weather_sender.clone(),
+            &self.config.weather,
+            generation,
+        )?;
         loop {
             select! {
-                recv(sig_receiver) -> _ => {
-                    return Ok(());
+                recv(sig_receiver) -> request => {
+                    let mut next = Some(request?);
+                    while let Some(request) = next.take() {
+                        match request {
+                            SignalRequest::Shutdown => return Ok(()),
+                            SignalRequest::Reload => match self.reload_config() {
+                                Ok(new_provider) => {
+                                    generation += 1;
+                                    provider = Arc::new(new_provider);
+                                    render = tick(self.config.leds.frame_interval());
+                                    update_pollen_count =
+                                        tick(self.config.pollen.refresh_interval());
+                                    update_weather =
+                                        tick(self.config.weather.refresh_interval());
+                                    _pollen_fetch = App::update_pollen_count(
+                                        supervisor,
+                                        pollen_sender.clone(),
+                                        provider.clone(),
+                                        self.config.pollen.retry.clone(),
+                                        generation,
+                                    )?;
+                                    _weather_fetch = App::update_weather(
+                                        supervisor,
+                                        weather_sender.clone(),
+                                        &self.config.weather,
+                                        generation,
+                                    )?;
+                                }
+                                Err(e) => eprintln!("Keeping the old config: {}", e),
+                            },
+                            SignalRequest::DumpState => {
+                                self.log_state(supervisor, &provider, should_render);
+                            }
+                            SignalRequest::NextMode => {
+                                self.display.next_mode();
+                                println!("Display mode: {}", self.display.active());
+                            }
+                            SignalRequest::SelfTest => {
+                                // Signals still work during the pattern, stopping it early
+                                let frames = test_pattern::quick_test(self.config.leds.count);
+                                let played =
+                                    test_pattern::play(&mut self.interface, &frames, &sig_receiver);
+                                match played {
+                                    Ok(interrupted) => next = interrupted,
+                                    Err(e) => eprintln!("Self test failed: {}", e),
+                                }
+                            }
+                        }
+                    }
                 }
                 recv(render) -> _ => {
+                    self.report_worker_failures(supervisor.reap());
                     if should_render {
-                        self.led_clock.update()?;
-                        self.interface.write(&self.led_clock)?.flush()?;
+                        self.render()?;
                     }
                 }
                 recv(pollen_receiver) -> pollen_result => {
                     match pollen_result {
-                        Ok(pollen_count) => self.led_clock.set_background(pollen_count.into()),
+                        Ok((fetched, forecast)) if fetched == generation => {
+                            self.receive_forecast(forecast)
+                        }
+                        Ok(_) => println!("Ignoring a pollen forecast from before the reload"),
                         Err(e) => return Err(e.into()),
                     };
                 }
                 recv(update_pollen_count) -> _ => {
-                    App::update_pollen_count(pollen_sender.clone());
+                    _pollen_fetch = App::update_pollen_count(
+                        supervisor,
+                        pollen_sender.clone(),
+                        provider.clone(),
+                        self.config.pollen.retry.clone(),
+                        generation,
+                    )?;
+                }
+                recv(weather_receiver) -> forecast => {
+                    match forecast? {
+                        (fetched, forecast) if fetched == generation => {
+                            self.receive_weather(forecast)
+                        }
+                        _ => println!("Ignoring a weather forecast from before the reload"),
+                    }
+                }
+                recv(update_weather) -> _ => {
+                    _weather_fetch = App::update_weather(
+                        supervisor,
+                        weather_sender.clone(),
+                        &self.config.weather,
+                        generation,
+                    )?;
                 }
                 recv(pir_receiver) -> pir_detection => {
                     match pir_detection {
                         Ok(true) => should_render = true,
-                        Ok(false) => timeout_render = Some(after(Duration::from_secs(10))),
+                        Ok(false) => timeout_render = Some(after(self.config.pir.timeout())),
                         Err(e) => return Err(e.into()),
                     }
                 }
@@ -110,3 +551,96 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led::{LedValue, RecordingBackend};
+    use crate::pollen::PollenCount;
+
+    #[test]
+    fn render_sends_a_full_clock_face_to_the_backend() {
+        let recording = RecordingBackend::new();
+        let interface = LedInterface::new(24, Box::new(recording.clone())).unwrap();
+        let mut config = Config::default();
+        config.pollen.cache = false;
+        // Full brightness whatever time the test runs
+        config.brightness.night_level = 1.0;
+        let mut app =
+            App::with_interface(interface, ErrorHandler::new(None), Cli::default(), config);
+        app.receive_forecast(Some(vec![(Local::today().naive_local(), PollenCount::Low)]));
+        app.render().unwrap();
+
+        let frame = recording.last_frame().unwrap();
+        assert_eq!(frame.len(), 24);
+        let background: LedValue = Some(PollenCount::Low).into();
+        // Two LEDs for each hand, smooth hands are shared between neighbours
+        assert!(frame.iter().filter(|led| **led == background).count() >= 18);
+    }
+
+    #[test]
+    fn reloading_for_another_weather_site_forgets_the_forecast() {
+        let dir = std::env::temp_dir().join(format!("flower-reload-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("flower.toml");
+        let write_config = |site_id: u32| {
+            let text = format!(
+                "[pollen]\ncache = false\n[weather]\napi_key = \"abc\"\nsite_id = {}",
+                site_id
+            );
+            std::fs::write(&path, text).unwrap();
+        };
+        write_config(310069);
+        let cli = Cli {
+            config: Some(path.clone()),
+            ..Default::default()
+        };
+        let config = Config::load(&cli).unwrap();
+        let interface = LedInterface::new(24, Box::new(RecordingBackend::new())).unwrap();
+        let mut app = App::with_interface(interface, ErrorHandler::new(None), cli, config);
+        let weather = met_api::parse_forecast(include_str!("../fixtures/weather/forecast.json"));
+        app.receive_weather(weather.unwrap());
+
+        app.reload_config().unwrap();
+        assert!(app.weather.is_some());
+        write_config(3772);
+        app.reload_config().unwrap();
+        assert!(app.weather.is_none());
+        assert!(app
+            .state()
+            .contains(&"Weather forecast: none yet".to_string()));
+
+        // The fetch for the old site finishing late
+        let weather = met_api::parse_forecast(include_str!("../fixtures/weather/forecast.json"));
+        app.receive_weather(weather.unwrap());
+        assert!(app.weather.is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn state_includes_the_forecast() {
+        let recording = RecordingBackend::new();
+        let interface = LedInterface::new(24, Box::new(recording)).unwrap();
+        let mut config = Config::default();
+        config.pollen.cache = false;
+        config.weather.api_key = Some("abc".to_string());
+        config.weather.site_id = Some(310069);
+        let mut app =
+            App::with_interface(interface, ErrorHandler::new(None), Cli::default(), config);
+        assert!(app
+            .state()
+            .contains(&"Pollen forecast: none yet".to_string()));
+
+        let today = Local::today().naive_local();
+        app.receive_forecast(Some(vec![(today, PollenCount::Low)]));
+        assert!(app.state().contains(&format!("  {}: Low", today)));
+
+        let weather = met_api::parse_forecast(include_str!("../fixtures/weather/forecast.json"));
+        app.receive_weather(weather.unwrap());
+        assert!(app
+            .state()
+            .iter()
+            .any(|line| line.starts_with("Weather forecast for EXETER (310069) issued at")));
+    }
+}
@@ -2,65 +2,62 @@ mod forecast;
 mod location;
 mod sane;
 
+use crate::error::{FlowerError, Result};
 use isahc::prelude::*;
+use std::convert::TryInto;
 
-pub use location::{Location, LocationId};
+use forecast::ForecastResponse;
+pub use location::Location;
 use location::LocationsResponse;
-use crate::met_api::forecast::ForecastResponse;
+#[cfg(test)]
+pub use sane::SaneLocation;
+pub use sane::{SaneForecast, SaneForecastUnit};
 
 const MET_BASE: &str = "http://datapoint.metoffice.gov.uk/public/data";
 
+/// Reads a 3 hourly DataPoint forecast
+pub fn parse_forecast(json: &str) -> Result<SaneForecast> {
+    let response: ForecastResponse = serde_json::from_str(json)?;
+    Ok(response.try_into()?)
+}
+
+/// The Met Office DataPoint API, which needs a (free) key
 pub struct MetApi {
     pub api_key: String,
 }
 
 impl MetApi {
-    pub fn new() -> MetApi {
-        MetApi {
-            api_key: std::env::var("API_KEY").expect("API_KEY not found in environment"),
-        }
-    }
-
-    fn make_request(&self, uri: String) -> Response<Body> {
-        isahc::get(uri)
-            .expect("Could not contact met office")
+    pub fn new(api_key: String) -> MetApi {
+        MetApi { api_key }
     }
 
-    pub fn forecast_site_list(&self) -> Vec<Location> {
-        let response: LocationsResponse = self.make_request(format!(
-            "{}/val/wxfcs/all/json/sitelist?key={}",
-            MET_BASE, self.api_key
-        ))
-        .json()
-        .expect("Could not read response");
-        response.locations.location
-    }
-
-    pub fn forecast_capabilities(&self) -> String {
+    /// Gets `resource` from the forecast API, with `options` such as "&res=3hourly" added to
+    /// the query. Errors don't include the URL, it has the key in it.
+    fn make_request(&self, resource: &str, options: &str) -> Result<String> {
         let uri = format!(
-            "{}/val/wxfcs/all/json/capabilities?key={}",
-            MET_BASE, self.api_key
+            "{}/val/wxfcs/all/json/{}?key={}{}",
+            MET_BASE, resource, self.api_key, options
         );
-        println!("{}", uri);
-        self.make_request(uri)
-            .text()
-            .expect("Could not read response")
+        let mut response = isahc::get(uri)?;
+        if !response.status().is_success() {
+            return Err(FlowerError::SimpleError(format!(
+                "DataPoint answered {}",
+                response.status()
+            ))
+            .into());
+        }
+        Ok(response.text()?)
+    }
+
+    /// Every site there are forecasts for
+    pub fn forecast_site_list(&self) -> Result<Vec<Location>> {
+        let response: LocationsResponse =
+            serde_json::from_str(&self.make_request("sitelist", "")?)?;
+        Ok(response.locations.location.into_vec())
     }
 
-    pub fn forecast(&self, location_id: LocationId) -> ForecastResponse {
-        let text = self.make_request(format!(
-            "{}/val/wxfcs/all/json/{}?res=3hourly&key={}",
-            MET_BASE, location_id, self.api_key
-        ))
-            .text()
-            .expect("couldn't read");
-        println!("{:#?}", text);
-        let forecast: ForecastResponse = self.make_request(format!(
-            "{}/val/wxfcs/all/json/{}?res=3hourly&key={}",
-            MET_BASE, location_id, self.api_key
-        ))
-        .json()
-        .expect("Could not read response");
-        forecast
+    /// The forecast every 3 hours over the next 5 days for the site with id `site_id`
+    pub fn forecast(&self, site_id: u32) -> Result<SaneForecast> {
+        parse_forecast(&self.make_request(&site_id.to_string(), "&res=3hourly")?)
     }
 }
@@ -1,23 +1,25 @@
 use serde::Deserialize;
-use serde_json::Value;
-use std::fmt;
-use std::collections::HashMap;
-use crate::met_api::Location;
 
-pub enum Resolution {
-    ThreeHourly,
-    Daily,
+/// DataPoint gives a list with only one thing in it as just that thing, rather than a list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
 }
 
-impl fmt::Display for Resolution {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl<T> OneOrMany<T> {
+    pub fn into_vec(self) -> Vec<T> {
         match self {
-            Self::ThreeHourly => write!(f, "3hourly"),
-            Self::Daily => write!(f, "daily"),
+            Self::One(one) => vec![one],
+            Self::Many(many) => many,
         }
     }
 }
 
+// The same forecast as XML, the JSON has the same structure with attributes as fields and the
+// text as "$". Only the parts the flower uses are read.
+//
 // <?xml version="1.0" encoding="ISO-8859-1"?>
 // <SiteRep>
 // <Wx>
@@ -93,33 +95,14 @@ pub struct ForecastResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct SiteRep {
-    #[serde(rename = "Wx")]
-    pub wx: Params,
     #[serde(rename = "DV")]
     pub dv: Dv,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct Params {
-    #[serde(rename = "Param")]
-    pub param: Vec<Param>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct Param {
-    pub name: String,
-    pub units: String,
-    #[serde(rename = "$")]
-    pub description: String,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "PascalCase")]
 pub struct Dv {
     #[serde(rename = "dataDate")]
     pub data_date: String,
-    #[serde(rename = "type")]
-    pub data_type: String,
     #[serde(rename = "Location")]
     pub location: ForecastLocation,
 }
@@ -127,37 +110,27 @@ pub struct Dv {
 #[derive(Debug, Deserialize)]
 pub struct ForecastLocation {
     pub i: String,
-    pub lat: String,
-    pub lon: String,
     pub name: String,
-    pub country: String,
-    pub continent: String,
     #[serde(rename = "Period")]
-    pub period: Vec<ForecastPeriod>,
+    pub period: OneOrMany<ForecastPeriod>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ForecastPeriod {
-    #[serde(rename = "type")]
-    pub period_type: String,
+    /// The day, eg "2012-11-19Z"
     pub value: String,
     #[serde(rename = "Rep")]
-    pub rep: Vec<Rep>,
+    pub rep: OneOrMany<Rep>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Rep {
+    /// Precipitation probability, %
     pub pp: String,
-    pub s: String,
-    pub d: String,
-    pub w: String,
-    pub h: String,
+    /// Temperature, C
     pub t: String,
+    /// Minutes after midnight UTC
     #[serde(rename = "$")]
     pub dollar: String,
-    pub g: String,
-    pub u: String,
-    pub v: String,
-    pub f: String,
 }
@@ -1,29 +1,12 @@
-use serde::export::Formatter;
+use crate::met_api::forecast::OneOrMany;
 use serde::Deserialize;
-use std::fmt;
-
-pub enum LocationId {
-    All,
-    Location(u32),
-}
-
-impl fmt::Display for LocationId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::All => write!(f, "all"),
-            LocationId::Location(loc) => write!(f, "{}", loc),
-        }
-    }
-}
 
+/// A site DataPoint has forecasts for, from the site list
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
 pub struct Location {
     pub id: String,
     pub name: String,
-    pub latitude: String,
-    pub longitude: String,
-    pub elevation: Option<String>,
     pub region: Option<String>,
     pub unitary_auth_area: Option<String>,
 }
@@ -37,5 +20,5 @@ pub struct LocationsResponse {
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Locations {
-    pub location: Vec<Location>,
+    pub location: OneOrMany<Location>,
 }
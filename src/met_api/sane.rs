@@ -1,54 +1,174 @@
-use crate::met_api::forecast::{ForecastLocation, Dv, ForecastPeriod, Rep};
+use crate::error::FlowerError;
+use crate::met_api::forecast::{ForecastLocation, ForecastResponse, Rep};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
 use std::convert::{TryFrom, TryInto};
 use std::num::ParseIntError;
 
+/// How far either side of a forecast it still stands for, half the 3 hours between them
+const HALF_STEP_MINS: i64 = 90;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct SaneLocation {
     pub id: u32,
     pub name: String,
-    pub latitude: String,
-    pub longitude: String,
 }
 
-impl TryFrom<ForecastLocation> for SaneLocation {
+impl TryFrom<&ForecastLocation> for SaneLocation {
     type Error = ParseIntError;
 
-    fn try_from(forecast_location: ForecastLocation) -> Result<Self, Self::Error> {
+    fn try_from(forecast_location: &ForecastLocation) -> Result<Self, Self::Error> {
         Ok(Self {
             id: forecast_location.i.parse()?,
-            name: forecast_location.name,
-            latitude: forecast_location.lat,
-            longitude: forecast_location.lon,
+            name: forecast_location.name.clone(),
         })
     }
 }
 
+/// The weather forecast for one time
+#[derive(Clone, Debug, PartialEq)]
 pub struct SaneForecastUnit {
-    feels_like_temperature: String,
-    wind_gust: String,
-    screen_relative_humidity: String,
-    temperature: String,
-    visibility: String,
-    wind_direction: String,
-    wind_speed: String,
-    max_uv_index: String,
-    weather_type: String,
-    precipitation_probability: String,
-}
-
-impl From<Rep> for SaneForecastUnit {
-    fn from(rep: Rep) -> Self {
-        Self {
-            feels_like_temperature: rep.f,
-            wind_gust: rep.g,
-            screen_relative_humidity: rep.h,
-            temperature: rep.t,
-            visibility: rep.v,
-            wind_direction: rep.d,
-            wind_speed: rep.s,
-            max_uv_index: rep.u,
-            weather_type: rep.w,
-            precipitation_probability: rep.pp,
+    pub time: DateTime<Utc>,
+    /// In degrees C
+    pub temperature: f64,
+    /// The chance of rain, snow or hail, from 0 to 100
+    pub precipitation_probability: f64,
+}
+
+impl SaneForecastUnit {
+    fn from_rep(day: NaiveDate, rep: &Rep) -> Result<Self, FlowerError> {
+        let number = |name: &str, value: &str| {
+            value.parse::<f64>().map_err(|_| {
+                FlowerError::SimpleError(format!("{} ({}) is not a number", name, value))
+            })
+        };
+        let minutes = number("Rep minutes", &rep.dollar)?;
+        Ok(Self {
+            time: Utc.from_utc_datetime(&day.and_hms(0, 0, 0)) + Duration::minutes(minutes as i64),
+            temperature: number("T", &rep.t)?,
+            precipitation_probability: number("Pp", &rep.pp)?,
+        })
+    }
+}
+
+/// A forecast for one site every 3 hours over the next few days
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaneForecast {
+    pub location: SaneLocation,
+    pub issued_at: DateTime<Utc>,
+    /// In time order
+    pub units: Vec<SaneForecastUnit>,
+}
+
+impl TryFrom<ForecastResponse> for SaneForecast {
+    type Error = FlowerError;
+
+    fn try_from(response: ForecastResponse) -> Result<Self, Self::Error> {
+        let dv = response.site_rep.dv;
+        let location = (&dv.location).try_into().map_err(|_| {
+            FlowerError::SimpleError(format!("site id {} is not a number", dv.location.i))
+        })?;
+        let issued_at = DateTime::parse_from_rfc3339(&dv.data_date)
+            .map_err(|_| FlowerError::SimpleError(format!("{} is not a time", dv.data_date)))?
+            .with_timezone(&Utc);
+        let mut units = vec![];
+        for period in dv.location.period.into_vec() {
+            let day = NaiveDate::parse_from_str(period.value.trim_end_matches('Z'), "%Y-%m-%d")
+                .map_err(|_| FlowerError::SimpleError(format!("{} is not a day", period.value)))?;
+            for rep in period.rep.into_vec() {
+                units.push(SaneForecastUnit::from_rep(day, &rep)?);
+            }
+        }
+        units.sort_by_key(|unit| unit.time);
+        Ok(Self {
+            location,
+            issued_at,
+            units,
+        })
+    }
+}
+
+impl SaneForecast {
+    /// `value` of the forecast at `time`, in proportion between the forecasts either side of
+    /// it. Near the first or last forecast it is that forecast's value, further out there is
+    /// no forecast.
+    pub fn at<F>(&self, time: DateTime<Utc>, value: F) -> Option<f64>
+    where
+        F: Fn(&SaneForecastUnit) -> f64,
+    {
+        let half_step = Duration::minutes(HALF_STEP_MINS);
+        match self.units.iter().position(|unit| unit.time >= time) {
+            Some(0) => {
+                let first = &self.units[0];
+                Some(value(first)).filter(|_| first.time - time <= half_step)
+            }
+            Some(after) => {
+                let (before, after) = (&self.units[after - 1], &self.units[after]);
+                let fraction = (time - before.time).num_seconds() as f64
+                    / (after.time - before.time).num_seconds() as f64;
+                Some(value(before) + (value(after) - value(before)) * fraction)
+            }
+            None => {
+                let last = self.units.last()?;
+                Some(value(last)).filter(|_| time - last.time <= half_step)
+            }
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::met_api::parse_forecast;
+
+    // Written by hand, see the readme in fixtures/weather
+    const FORECAST: &str = include_str!("../../fixtures/weather/forecast.json");
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2012, 11, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn the_forecast_is_read_in_time_order() {
+        let forecast = parse_forecast(FORECAST).unwrap();
+        assert_eq!(
+            forecast.location,
+            SaneLocation {
+                id: 310069,
+                name: "EXETER".to_string()
+            }
+        );
+        assert_eq!(forecast.issued_at, utc(19, 14, 0));
+        assert_eq!(forecast.units.len(), 14);
+        assert_eq!(
+            forecast.units[0],
+            SaneForecastUnit {
+                time: utc(19, 9, 0),
+                temperature: 11.0,
+                precipitation_probability: 16.0,
+            }
+        );
+        // The last day has a single forecast, which DataPoint doesn't put in a list
+        assert_eq!(forecast.units[13].time, utc(21, 0, 0));
+    }
+
+    #[test]
+    fn values_are_interpolated_between_forecasts() {
+        let forecast = parse_forecast(FORECAST).unwrap();
+        let rain = |time| forecast.at(time, |unit| unit.precipitation_probability);
+        assert_eq!(rain(utc(20, 3, 0)), Some(96.0));
+        // A third of the way from 96% at 3am to 97% at 6am
+        assert_eq!(rain(utc(20, 4, 0)).map(f64::round), Some(96.0));
+        assert_eq!(rain(utc(20, 13, 0)).map(f64::round), Some(50.0));
+
+        assert_eq!(rain(utc(19, 7, 30)), Some(16.0));
+        assert_eq!(rain(utc(19, 7, 29)), None);
+        assert_eq!(rain(utc(21, 1, 30)), Some(11.0));
+        assert_eq!(rain(utc(21, 1, 31)), None);
+    }
+
+    #[test]
+    fn a_rep_that_is_not_a_number_is_an_error() {
+        let broken = FORECAST.replacen("\"T\": \"11\"", "\"T\": \"eleven\"", 1);
+        assert!(parse_forecast(&broken).is_err());
+    }
+}
@@ -1,21 +1,24 @@
+use crate::supervisor::Supervisor;
 use crate::Result;
 use crossbeam_channel::{bounded, Receiver};
 use rppal::gpio::{Gpio, Level, Trigger};
-use std::{thread, thread::JoinHandle};
+use std::time::Duration;
+
+/// How often the sensor thread looks up from the pin to see if it should stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(250);
 
 pub struct PassiveInfraRedSensor {
-    handle: JoinHandle<Result<()>>,
     receiver: Receiver<bool>,
 }
 
 impl PassiveInfraRedSensor {
-    pub fn new(pin: u8) -> Result<Self> {
+    pub fn new(pin: u8, supervisor: &mut Supervisor) -> Result<Self> {
         let (sender, receiver) = bounded::<bool>(1);
-        let handle = thread::spawn(move || {
+        supervisor.spawn("pir", move |stop| {
             let mut input_pin = Gpio::new()?.get(pin)?.into_input();
             input_pin.set_interrupt(Trigger::Both)?;
-            loop {
-                let level = input_pin.poll_interrupt(false, None)?;
+            while !stop.is_stopped() {
+                let level = input_pin.poll_interrupt(false, Some(STOP_CHECK_INTERVAL))?;
                 match level {
                     Some(Level::High) => {
                         let _ = sender.send(true);
@@ -26,8 +29,9 @@ impl PassiveInfraRedSensor {
                     _ => {}
                 }
             }
-        });
-        Ok(Self { handle, receiver })
+            Ok(())
+        })?;
+        Ok(Self { receiver })
     }
 
     pub fn get_receiver(self) -> Receiver<bool> {
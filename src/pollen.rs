@@ -1,5 +1,10 @@
+mod cache;
+mod provider;
+mod region;
+
 use crate::error::FlowerError;
 use crate::Result;
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
 use core::{
     convert::{TryFrom, TryInto},
     fmt,
@@ -7,8 +12,13 @@ use core::{
 use isahc::config::RedirectPolicy;
 use isahc::prelude::*;
 use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
 use std::error::Error as StdError;
 
+pub use cache::{CachedForecast, PollenCache};
+pub use provider::{FallbackProvider, PollenProvider};
+pub use region::PollenRegion;
+
 #[derive(Debug)]
 pub struct PollenParseError(String);
 
@@ -20,19 +30,25 @@ impl fmt::Display for PollenParseError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PollenCount {
+    VeryHigh,
     High,
     Medium,
     Low,
+    /// Outside of the pollen season the Met Office doesn't publish a count
+    NotAvailable,
 }
 
 impl fmt::Display for PollenCount {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::VeryHigh => write!(f, "Very High"),
             Self::High => write!(f, "High"),
             Self::Medium => write!(f, "Medium"),
             Self::Low => write!(f, "Low"),
+            Self::NotAvailable => write!(f, "Not Available"),
         }
     }
 }
@@ -42,14 +58,31 @@ impl TryFrom<&str> for PollenCount {
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
         match value {
+            "vh" => Ok(PollenCount::VeryHigh),
             "h" => Ok(PollenCount::High),
             "m" => Ok(PollenCount::Medium),
             "l" => Ok(PollenCount::Low),
+            "na" | "n/a" => Ok(PollenCount::NotAvailable),
             x => Err(PollenParseError(x.to_string())),
         }
     }
 }
 
+/// The pollen count for each day the Met Office has a forecast for, starting with the day the
+/// forecast was published
+pub type PollenForecast = Vec<(NaiveDate, PollenCount)>;
+
+/// Finds the pollen count for a given day in a forecast
+pub fn pollen_count_on(
+    forecast: &[(NaiveDate, PollenCount)],
+    date: NaiveDate,
+) -> Option<PollenCount> {
+    forecast
+        .iter()
+        .find(|(day, _)| *day == date)
+        .map(|(_, pollen_count)| *pollen_count)
+}
+
 const POLLEN_URL: &str =
     "https://metoffice.gov.uk/weather/warnings-and-advice/seasonal-advice/pollen-forecast";
 
@@ -61,25 +94,308 @@ fn get_html() -> Result<String> {
         .text()?)
 }
 
-pub fn get_pollen_count() -> Result<PollenCount> {
+pub fn get_pollen_forecast(region: PollenRegion) -> Result<PollenForecast> {
     let html = get_html()?;
+    parse_pollen_forecast(&html, region, Local::today().naive_local())
+}
+
+/// How many days either side of the day it was fetched a page can be dated
+const MAX_PAGE_AGE_DAYS: i64 = 3;
+
+/// The date of the first column on the page, from the names of the days in the region's table.
+/// The first columns are "Today" and "Tomorrow", which could be any day, so it is worked out
+/// from the first column with a day of the week and is the nearest such day to `today`. A page
+/// fetched after midnight but before the Met Office publishes the next day's forecast starts
+/// the day before `today`.
+fn first_day(labels: &[String], today: NaiveDate) -> Result<NaiveDate> {
+    let mut first_weekday = None;
+    for (column, label) in labels.iter().enumerate() {
+        let weekday = match label.split_whitespace().next().map(str::parse::<Weekday>) {
+            Some(Ok(weekday)) => weekday,
+            _ => continue,
+        };
+        let weekday = (weekday.num_days_from_monday() as i64 - column as i64).rem_euclid(7);
+        if first_weekday.is_some() && first_weekday != Some(weekday) {
+            return Err(FlowerError::SimpleError(format!(
+                "The days on the page are out of order: {}",
+                labels.join(", ")
+            ))
+            .into());
+        }
+        first_weekday = Some(weekday);
+    }
+    let first_weekday = first_weekday.ok_or_else(|| {
+        FlowerError::SimpleError(format!("No day names found in {}", labels.join(", ")))
+    })?;
+    Ok((-MAX_PAGE_AGE_DAYS..=MAX_PAGE_AGE_DAYS)
+        .map(|days| today + Duration::days(days))
+        .find(|day| day.weekday().num_days_from_monday() as i64 == first_weekday)
+        .expect("seven days in a row have every day of the week"))
+}
 
-    let document = Html::parse_document(html.as_str());
+/// Reads a region's forecast from the Met Office pollen page fetched on `today`. Each day is
+/// dated from the day names on the page.
+pub fn parse_pollen_forecast(
+    html: &str,
+    region: PollenRegion,
+    today: NaiveDate,
+) -> Result<PollenForecast> {
+    let document = Html::parse_document(html);
     //*[@id="se"]/table/tbody/tr/td[1]/div/span
-    let se_selector = Selector::parse("#se")
-        .map_err(|_| FlowerError::SimpleError("Could not create #se parser".to_string()))?;
-    let se = document
-        .select(&se_selector)
+    let region_id = format!("#{}", region.id());
+    let region_selector = Selector::parse(&region_id)
+        .map_err(|_| FlowerError::SimpleError(format!("Could not create {} parser", region_id)))?;
+    let region_element = document
+        .select(&region_selector)
         .next()
-        .ok_or_else(|| FlowerError::SimpleError("#se not found on page".to_string()))?;
+        .ok_or_else(|| FlowerError::SimpleError(format!("{} not found on page", region_id)))?;
 
-    let span_selector = Selector::parse("span")
-        .map_err(|_| FlowerError::SimpleError("Could not create #se parser".to_string()))?;
-    let today = se.select(&span_selector).next().ok_or_else(|| {
-        FlowerError::SimpleError("today span (the first span) was not found".to_string())
-    })?;
-    let pollen_indicator = today.value().attr("data-category").ok_or_else(|| {
-        FlowerError::SimpleError("No data-category attribute found on today span".to_string())
-    })?;
-    Ok(pollen_indicator.try_into()?)
+    // The table's heading names each day, and there is one span per day in the same order
+    let label_selector = Selector::parse("thead th")
+        .map_err(|_| FlowerError::SimpleError("Could not create heading parser".to_string()))?;
+    let day_selector = Selector::parse("span[data-category]")
+        .map_err(|_| FlowerError::SimpleError("Could not create span parser".to_string()))?;
+    let spans: Vec<_> = region_element.select(&day_selector).collect();
+    if spans.is_empty() {
+        return Err(FlowerError::SimpleError(format!(
+            "No days with a data-category attribute found in {}",
+            region_id
+        ))
+        .into());
+    }
+    let labels: Vec<String> = region_element
+        .select(&label_selector)
+        .map(|label| label.text().collect::<String>().trim().to_string())
+        .collect();
+    let first_day = first_day(&labels, today)?;
+    spans
+        .iter()
+        .enumerate()
+        .map(|(day, span)| {
+            let pollen_indicator = span.value().attr("data-category").unwrap_or_default();
+            Ok((
+                first_day + Duration::days(day as i64),
+                pollen_indicator.try_into()?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::region::ALL_REGIONS;
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    // Written by hand, see the readme in fixtures/pollen/synthetic
+    const FORECAST: &str = include_str!("../fixtures/pollen/synthetic/forecast.html");
+    const OUT_OF_SEASON: &str = include_str!("../fixtures/pollen/synthetic/out_of_season.html");
+    const RENAMED_REGIONS: &str = include_str!("../fixtures/pollen/synthetic/renamed_regions.html");
+    const RENAMED_ATTRIBUTE: &str =
+        include_str!("../fixtures/pollen/synthetic/renamed_attribute.html");
+    const UNKNOWN_CATEGORY: &str =
+        include_str!("../fixtures/pollen/synthetic/unknown_category.html");
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2020, 6, 16)
+    }
+
+    fn counts(forecast: &[(NaiveDate, PollenCount)]) -> Vec<PollenCount> {
+        forecast.iter().map(|(_, count)| *count).collect()
+    }
+
+    #[test]
+    fn every_region_is_read_from_the_forecast() {
+        use PollenCount::*;
+        let expected = [
+            (PollenRegion::Scotland, [Low, Low, Medium, Medium, Low]),
+            (
+                PollenRegion::NorthernIreland,
+                [Low, Medium, Medium, High, Medium],
+            ),
+            (PollenRegion::Wales, [Medium, Medium, High, High, Medium]),
+            (PollenRegion::NorthEast, [Medium, High, High, Medium, Low]),
+            (
+                PollenRegion::NorthWest,
+                [High, High, VeryHigh, High, Medium],
+            ),
+            (
+                PollenRegion::YorkshireAndHumber,
+                [High, VeryHigh, VeryHigh, High, High],
+            ),
+            (
+                PollenRegion::EastMidlands,
+                [VeryHigh, VeryHigh, High, Medium, Medium],
+            ),
+            (
+                PollenRegion::WestMidlands,
+                [VeryHigh, High, High, High, Medium],
+            ),
+            (PollenRegion::East, [High, Medium, Low, Low, Medium]),
+            (
+                PollenRegion::SouthEast,
+                [VeryHigh, VeryHigh, VeryHigh, High, High],
+            ),
+            (PollenRegion::SouthWest, [Medium, Low, Low, Medium, High]),
+        ];
+        assert_eq!(expected.len(), ALL_REGIONS.len());
+        for (region, days) in expected.iter() {
+            let forecast = parse_pollen_forecast(FORECAST, *region, today()).unwrap();
+            assert_eq!(counts(&forecast), days.to_vec(), "{}", region);
+        }
+    }
+
+    #[test]
+    fn days_are_dated_from_today() {
+        let forecast = parse_pollen_forecast(FORECAST, PollenRegion::SouthEast, today()).unwrap();
+        let dates: Vec<NaiveDate> = forecast.iter().map(|(date, _)| *date).collect();
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd(2020, 6, 16),
+                NaiveDate::from_ymd(2020, 6, 17),
+                NaiveDate::from_ymd(2020, 6, 18),
+                NaiveDate::from_ymd(2020, 6, 19),
+                NaiveDate::from_ymd(2020, 6, 20),
+            ]
+        );
+        assert_eq!(
+            pollen_count_on(&forecast, NaiveDate::from_ymd(2020, 6, 19)),
+            Some(PollenCount::High)
+        );
+        assert_eq!(
+            pollen_count_on(&forecast, NaiveDate::from_ymd(2020, 6, 21)),
+            None
+        );
+    }
+
+    #[test]
+    fn a_page_fetched_before_the_next_forecast_is_dated_when_it_was_published() {
+        // Just after midnight the page still shows yesterday as "Today"
+        let next_day = NaiveDate::from_ymd(2020, 6, 17);
+        let forecast = parse_pollen_forecast(FORECAST, PollenRegion::SouthEast, next_day).unwrap();
+        assert_eq!(forecast[0].0, today());
+        assert_eq!(
+            pollen_count_on(&forecast, next_day),
+            Some(PollenCount::VeryHigh)
+        );
+        assert_eq!(
+            pollen_count_on(&forecast, NaiveDate::from_ymd(2020, 6, 19)),
+            Some(PollenCount::High)
+        );
+        // A page from the day before it was fetched is still the same page
+        let day_before = NaiveDate::from_ymd(2020, 6, 15);
+        let forecast = parse_pollen_forecast(FORECAST, PollenRegion::SouthEast, day_before);
+        assert_eq!(forecast.unwrap()[0].0, today());
+    }
+
+    #[test]
+    fn a_page_without_day_names_is_an_error() {
+        let unnamed = FORECAST
+            .replace("<th>Thursday</th>", "<th></th>")
+            .replace("<th>Friday</th>", "<th></th>")
+            .replace("<th>Saturday</th>", "<th></th>");
+        let error = parse_pollen_forecast(&unnamed, PollenRegion::SouthEast, today())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("No day names"), "{}", error);
+
+        let shuffled = FORECAST.replacen("<th>Friday</th>", "<th>Monday</th>", 1);
+        assert!(parse_pollen_forecast(&shuffled, PollenRegion::Scotland, today()).is_err());
+    }
+
+    #[test]
+    fn out_of_season_is_not_available() {
+        for region in ALL_REGIONS.iter() {
+            let forecast = parse_pollen_forecast(OUT_OF_SEASON, *region, today()).unwrap();
+            assert_eq!(counts(&forecast), vec![PollenCount::NotAvailable; 5]);
+        }
+    }
+
+    /// Ignored until pages saved from metoffice.gov.uk are added, see fixtures/pollen/README.md.
+    /// Run with `cargo test -- --ignored every_saved_page_can_be_read`.
+    #[test]
+    #[ignore]
+    fn every_saved_page_can_be_read() {
+        let saved = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/pollen/saved");
+        let (mut in_season, mut out_of_season) = (0, 0);
+        for entry in fs::read_dir(saved).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("html") {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy();
+            let fetched = NaiveDate::parse_from_str(name.get(..10).unwrap_or(""), "%Y-%m-%d")
+                .unwrap_or_else(|_| panic!("{} should start with the day it was saved", name));
+            let html = fs::read_to_string(&path).unwrap();
+            let mut seen = vec![];
+            for region in ALL_REGIONS.iter() {
+                let forecast = parse_pollen_forecast(&html, *region, fetched)
+                    .unwrap_or_else(|e| panic!("{} {}: {}", name, region, e));
+                let age = fetched - forecast[0].0;
+                assert!(age.num_days() == 0 || age.num_days() == 1, "{}", name);
+                seen.extend(counts(&forecast));
+            }
+            if seen.iter().all(|count| *count == PollenCount::NotAvailable) {
+                out_of_season += 1;
+            } else {
+                in_season += 1;
+            }
+        }
+        assert!(
+            in_season > 0 && out_of_season > 0,
+            "fixtures/pollen/saved needs a page saved in season and one out of season, it has \
+             {} and {}",
+            in_season,
+            out_of_season
+        );
+    }
+
+    #[test]
+    fn categories_are_parsed() {
+        let categories = [
+            ("vh", PollenCount::VeryHigh),
+            ("h", PollenCount::High),
+            ("m", PollenCount::Medium),
+            ("l", PollenCount::Low),
+            ("na", PollenCount::NotAvailable),
+            ("n/a", PollenCount::NotAvailable),
+        ];
+        for (category, count) in categories.iter() {
+            assert_eq!(PollenCount::try_from(*category).unwrap(), *count);
+        }
+        assert!(PollenCount::try_from("x").is_err());
+    }
+
+    #[test]
+    fn renamed_regions_are_an_error() {
+        let error = parse_pollen_forecast(RENAMED_REGIONS, PollenRegion::SouthEast, today())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("#se not found"), "{}", error);
+    }
+
+    #[test]
+    fn a_renamed_attribute_is_an_error() {
+        let error = parse_pollen_forecast(RENAMED_ATTRIBUTE, PollenRegion::SouthEast, today())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("data-category"), "{}", error);
+    }
+
+    #[test]
+    fn an_unknown_category_is_an_error() {
+        let error = parse_pollen_forecast(UNKNOWN_CATEGORY, PollenRegion::Scotland, today())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("vl"), "{}", error);
+    }
+
+    #[test]
+    fn a_page_that_is_not_the_forecast_is_an_error() {
+        assert!(parse_pollen_forecast("<html></html>", PollenRegion::SouthEast, today()).is_err());
+        assert!(parse_pollen_forecast("", PollenRegion::SouthEast, today()).is_err());
+    }
 }
@@ -0,0 +1,109 @@
+use crate::error::Result;
+use crate::pollen::{pollen_count_on, PollenCount, PollenForecast};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A forecast along with when it was fetched
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachedForecast {
+    pub fetched_at: DateTime<Utc>,
+    pub forecast: PollenForecast,
+}
+
+impl CachedForecast {
+    pub fn new(forecast: PollenForecast, fetched_at: DateTime<Utc>) -> CachedForecast {
+        CachedForecast {
+            fetched_at,
+            forecast,
+        }
+    }
+
+    pub fn count_on(&self, date: NaiveDate) -> Option<PollenCount> {
+        pollen_count_on(&self.forecast, date)
+    }
+
+    /// Whether the forecast is older than `max_age` at `now`
+    pub fn is_stale(&self, now: DateTime<Utc>, max_age: Duration) -> bool {
+        now - self.fetched_at > max_age
+    }
+}
+
+/// Keeps the last forecast on disk so it survives a reboot
+pub struct PollenCache {
+    path: PathBuf,
+}
+
+impl PollenCache {
+    pub fn new(path: PathBuf) -> PollenCache {
+        PollenCache { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The cached forecast, or None if nothing has been cached yet
+    pub fn load(&self) -> Result<Option<CachedForecast>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&self.path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn save(&self, cached: &CachedForecast) -> Result<()> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        // Write then rename so losing power part way through can't leave half a file
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string(cached)?)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::env;
+
+    fn cached() -> CachedForecast {
+        CachedForecast::new(
+            vec![
+                (NaiveDate::from_ymd(2020, 6, 16), PollenCount::High),
+                (NaiveDate::from_ymd(2020, 6, 17), PollenCount::Medium),
+            ],
+            Utc.ymd(2020, 6, 16).and_hms(6, 0, 0),
+        )
+    }
+
+    #[test]
+    fn a_saved_forecast_can_be_loaded() {
+        let path = env::temp_dir()
+            .join(format!("flower-test-{}", std::process::id()))
+            .join("pollen.json");
+        let cache = PollenCache::new(path.clone());
+        assert_eq!(cache.load().unwrap(), None);
+
+        cache.save(&cached()).unwrap();
+        assert_eq!(cache.load().unwrap(), Some(cached()));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn forecasts_go_stale() {
+        let cached = cached();
+        let max_age = Duration::hours(6);
+        assert!(!cached.is_stale(Utc.ymd(2020, 6, 16).and_hms(12, 0, 0), max_age));
+        assert!(cached.is_stale(Utc.ymd(2020, 6, 16).and_hms(12, 0, 1), max_age));
+        assert_eq!(
+            cached.count_on(NaiveDate::from_ymd(2020, 6, 17)),
+            Some(PollenCount::Medium)
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::config::{PollenConfig, ProviderConfig};
+use crate::error::{FlowerError, Result};
+use crate::pollen::{get_pollen_forecast, PollenCount, PollenForecast, PollenRegion};
+use chrono::{Local, NaiveDate};
+use isahc::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Somewhere a pollen forecast can come from
+pub trait PollenProvider: Send + Sync {
+    /// A short description for logs, eg "met office (se)"
+    fn name(&self) -> String;
+
+    fn forecast(&self) -> Result<PollenForecast>;
+}
+
+/// Scrapes the Met Office pollen page
+pub struct MetOfficeProvider {
+    region: PollenRegion,
+}
+
+impl MetOfficeProvider {
+    pub fn new(region: PollenRegion) -> MetOfficeProvider {
+        MetOfficeProvider { region }
+    }
+}
+
+impl PollenProvider for MetOfficeProvider {
+    fn name(&self) -> String {
+        format!("met office ({})", self.region)
+    }
+
+    fn forecast(&self) -> Result<PollenForecast> {
+        get_pollen_forecast(self.region)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonDay {
+    date: NaiveDate,
+    count: PollenCount,
+}
+
+/// Reads a forecast in the form `[{"date": "2020-06-16", "count": "high"}, ...]`
+pub fn parse_json_forecast(json: &str) -> Result<PollenForecast> {
+    let days: Vec<JsonDay> = serde_json::from_str(json)?;
+    Ok(days.into_iter().map(|day| (day.date, day.count)).collect())
+}
+
+/// Fetches a JSON forecast (see [parse_json_forecast]) from a URL
+pub struct JsonProvider {
+    url: String,
+}
+
+impl JsonProvider {
+    pub fn new(url: String) -> JsonProvider {
+        JsonProvider { url }
+    }
+}
+
+impl PollenProvider for JsonProvider {
+    fn name(&self) -> String {
+        format!("json ({})", self.url)
+    }
+
+    fn forecast(&self) -> Result<PollenForecast> {
+        let json = isahc::get(self.url.as_str())?.text()?;
+        parse_json_forecast(&json)
+    }
+}
+
+/// Reads a JSON forecast (see [parse_json_forecast]) from a local file
+pub struct FileProvider {
+    path: PathBuf,
+}
+
+impl FileProvider {
+    pub fn new(path: PathBuf) -> FileProvider {
+        FileProvider { path }
+    }
+}
+
+impl PollenProvider for FileProvider {
+    fn name(&self) -> String {
+        format!("file ({})", self.path.display())
+    }
+
+    fn forecast(&self) -> Result<PollenForecast> {
+        parse_json_forecast(&fs::read_to_string(&self.path)?)
+    }
+}
+
+/// Always gives the same count for today, useful as a last resort or when trying out colours
+pub struct FixedProvider {
+    count: PollenCount,
+}
+
+impl FixedProvider {
+    pub fn new(count: PollenCount) -> FixedProvider {
+        FixedProvider { count }
+    }
+}
+
+impl PollenProvider for FixedProvider {
+    fn name(&self) -> String {
+        format!("fixed ({})", self.count)
+    }
+
+    fn forecast(&self) -> Result<PollenForecast> {
+        Ok(vec![(Local::today().naive_local(), self.count)])
+    }
+}
+
+/// Asks each provider in turn until one of them answers
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn PollenProvider>>,
+    answered_by: Mutex<Option<String>>,
+}
+
+impl FallbackProvider {
+    pub fn new(providers: Vec<Box<dyn PollenProvider>>) -> FallbackProvider {
+        FallbackProvider {
+            providers,
+            answered_by: Mutex::new(None),
+        }
+    }
+
+    pub fn from_config(config: &PollenConfig) -> Result<FallbackProvider> {
+        let region = config.region()?;
+        let providers = config
+            .providers
+            .iter()
+            .map(|provider| -> Box<dyn PollenProvider> {
+                match provider {
+                    ProviderConfig::MetOffice => Box::new(MetOfficeProvider::new(region)),
+                    ProviderConfig::Json { url } => Box::new(JsonProvider::new(url.clone())),
+                    ProviderConfig::File { path } => Box::new(FileProvider::new(path.clone())),
+                    ProviderConfig::Fixed { count } => Box::new(FixedProvider::new(*count)),
+                }
+            })
+            .collect();
+        Ok(FallbackProvider::new(providers))
+    }
+
+    /// The name of the provider that gave the last forecast, if any did
+    pub fn answered_by(&self) -> Option<String> {
+        self.answered_by.lock().unwrap().clone()
+    }
+}
+
+impl PollenProvider for FallbackProvider {
+    fn name(&self) -> String {
+        let names: Vec<String> = self.providers.iter().map(|p| p.name()).collect();
+        format!("fallback [{}]", names.join(", "))
+    }
+
+    fn forecast(&self) -> Result<PollenForecast> {
+        let mut failures = vec![];
+        for provider in self.providers.iter() {
+            match provider.forecast() {
+                // A forecast with no days would show white, so it counts as not answering
+                Ok(forecast) if forecast.is_empty() => {
+                    failures.push(format!("{}: the forecast has no days", provider.name()))
+                }
+                Ok(forecast) => {
+                    *self.answered_by.lock().unwrap() = Some(provider.name());
+                    return Ok(forecast);
+                }
+                Err(e) => failures.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+        *self.answered_by.lock().unwrap() = None;
+        Err(FlowerError::SimpleError(format!(
+            "No pollen provider answered. {}",
+            failures.join(". ")
+        ))
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingProvider;
+
+    impl PollenProvider for FailingProvider {
+        fn name(&self) -> String {
+            "failing".to_string()
+        }
+
+        fn forecast(&self) -> Result<PollenForecast> {
+            Err(FlowerError::SimpleError("layout changed".to_string()).into())
+        }
+    }
+
+    #[test]
+    fn fallback_uses_the_first_provider_that_answers() {
+        let fallback = FallbackProvider::new(vec![
+            Box::new(FailingProvider),
+            Box::new(FixedProvider::new(PollenCount::Medium)),
+            Box::new(FixedProvider::new(PollenCount::High)),
+        ]);
+        let forecast = fallback.forecast().unwrap();
+        assert_eq!(forecast[0].1, PollenCount::Medium);
+        assert_eq!(fallback.answered_by(), Some("fixed (Medium)".to_string()));
+    }
+
+    struct EmptyProvider;
+
+    impl PollenProvider for EmptyProvider {
+        fn name(&self) -> String {
+            "empty".to_string()
+        }
+
+        fn forecast(&self) -> Result<PollenForecast> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn fallback_skips_an_empty_forecast() {
+        let fallback = FallbackProvider::new(vec![
+            Box::new(EmptyProvider),
+            Box::new(FixedProvider::new(PollenCount::Low)),
+        ]);
+        assert_eq!(fallback.forecast().unwrap()[0].1, PollenCount::Low);
+        assert_eq!(fallback.answered_by(), Some("fixed (Low)".to_string()));
+
+        let fallback = FallbackProvider::new(vec![Box::new(EmptyProvider)]);
+        let error = fallback.forecast().unwrap_err().to_string();
+        assert!(
+            error.contains("empty: the forecast has no days"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn fallback_reports_every_failure() {
+        let fallback =
+            FallbackProvider::new(vec![Box::new(FailingProvider), Box::new(FailingProvider)]);
+        let error = fallback.forecast().unwrap_err().to_string();
+        assert_eq!(error.matches("failing: ").count(), 2, "{}", error);
+        assert!(error.contains("layout changed"), "{}", error);
+        assert_eq!(fallback.answered_by(), None);
+    }
+
+    #[test]
+    fn json_forecasts_are_parsed() {
+        let forecast = parse_json_forecast(
+            r#"[{"date": "2020-06-16", "count": "very_high"}, {"date": "2020-06-17", "count": "low"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            forecast,
+            vec![
+                (NaiveDate::from_ymd(2020, 6, 16), PollenCount::VeryHigh),
+                (NaiveDate::from_ymd(2020, 6, 17), PollenCount::Low),
+            ]
+        );
+        assert!(parse_json_forecast(r#"[{"date": "2020-06-16", "count": "lots"}]"#).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use crate::error::FlowerError;
+use core::{convert::TryFrom, fmt, str::FromStr};
+use serde::Deserialize;
+
+/// The regions the Met Office publishes a pollen forecast for. Each one is an element on the
+/// pollen page with the region's id.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum PollenRegion {
+    Scotland,
+    NorthernIreland,
+    Wales,
+    NorthEast,
+    NorthWest,
+    YorkshireAndHumber,
+    EastMidlands,
+    WestMidlands,
+    East,
+    #[default]
+    SouthEast,
+    SouthWest,
+}
+
+pub const ALL_REGIONS: [PollenRegion; 11] = [
+    PollenRegion::Scotland,
+    PollenRegion::NorthernIreland,
+    PollenRegion::Wales,
+    PollenRegion::NorthEast,
+    PollenRegion::NorthWest,
+    PollenRegion::YorkshireAndHumber,
+    PollenRegion::EastMidlands,
+    PollenRegion::WestMidlands,
+    PollenRegion::East,
+    PollenRegion::SouthEast,
+    PollenRegion::SouthWest,
+];
+
+/// Towns spread across each region, a coordinate belongs to the region of the nearest town
+const TOWNS: [(f64, f64, PollenRegion); 84] = [
+    (55.95, -3.19, PollenRegion::Scotland),           // Edinburgh
+    (55.86, -4.25, PollenRegion::Scotland),           // Glasgow
+    (57.15, -2.09, PollenRegion::Scotland),           // Aberdeen
+    (57.48, -4.22, PollenRegion::Scotland),           // Inverness
+    (56.46, -2.97, PollenRegion::Scotland),           // Dundee
+    (55.07, -3.61, PollenRegion::Scotland),           // Dumfries
+    (55.61, -2.81, PollenRegion::Scotland),           // Galashiels
+    (58.21, -6.39, PollenRegion::Scotland),           // Stornoway
+    (58.98, -2.96, PollenRegion::Scotland),           // Kirkwall
+    (60.15, -1.15, PollenRegion::Scotland),           // Lerwick
+    (54.60, -5.93, PollenRegion::NorthernIreland),    // Belfast
+    (55.00, -7.32, PollenRegion::NorthernIreland),    // Derry
+    (54.34, -7.64, PollenRegion::NorthernIreland),    // Enniskillen
+    (54.18, -6.34, PollenRegion::NorthernIreland),    // Newry
+    (51.48, -3.18, PollenRegion::Wales),              // Cardiff
+    (51.58, -3.00, PollenRegion::Wales),              // Newport
+    (51.62, -3.94, PollenRegion::Wales),              // Swansea
+    (51.86, -4.31, PollenRegion::Wales),              // Carmarthen
+    (52.41, -4.08, PollenRegion::Wales),              // Aberystwyth
+    (52.51, -3.31, PollenRegion::Wales),              // Newtown
+    (53.23, -4.13, PollenRegion::Wales),              // Bangor
+    (53.05, -2.99, PollenRegion::Wales),              // Wrexham
+    (54.98, -1.61, PollenRegion::NorthEast),          // Newcastle
+    (54.91, -1.38, PollenRegion::NorthEast),          // Sunderland
+    (54.78, -1.57, PollenRegion::NorthEast),          // Durham
+    (54.57, -1.23, PollenRegion::NorthEast),          // Middlesbrough
+    (54.52, -1.55, PollenRegion::NorthEast),          // Darlington
+    (54.97, -2.10, PollenRegion::NorthEast),          // Hexham
+    (55.77, -2.00, PollenRegion::NorthEast),          // Berwick
+    (53.48, -2.24, PollenRegion::NorthWest),          // Manchester
+    (53.41, -2.98, PollenRegion::NorthWest),          // Liverpool
+    (53.19, -2.89, PollenRegion::NorthWest),          // Chester
+    (53.76, -2.70, PollenRegion::NorthWest),          // Preston
+    (53.82, -3.05, PollenRegion::NorthWest),          // Blackpool
+    (54.05, -2.80, PollenRegion::NorthWest),          // Lancaster
+    (54.33, -2.75, PollenRegion::NorthWest),          // Kendal
+    (54.89, -2.93, PollenRegion::NorthWest),          // Carlisle
+    (53.80, -1.55, PollenRegion::YorkshireAndHumber), // Leeds
+    (53.80, -1.75, PollenRegion::YorkshireAndHumber), // Bradford
+    (53.38, -1.47, PollenRegion::YorkshireAndHumber), // Sheffield
+    (53.52, -1.13, PollenRegion::YorkshireAndHumber), // Doncaster
+    (53.96, -1.08, PollenRegion::YorkshireAndHumber), // York
+    (53.74, -0.33, PollenRegion::YorkshireAndHumber), // Hull
+    (54.28, -0.40, PollenRegion::YorkshireAndHumber), // Scarborough
+    (52.95, -1.15, PollenRegion::EastMidlands),       // Nottingham
+    (52.92, -1.48, PollenRegion::EastMidlands),       // Derby
+    (52.64, -1.13, PollenRegion::EastMidlands),       // Leicester
+    (53.23, -0.54, PollenRegion::EastMidlands),       // Lincoln
+    (52.98, -0.02, PollenRegion::EastMidlands),       // Boston
+    (52.24, -0.90, PollenRegion::EastMidlands),       // Northampton
+    (53.26, -1.91, PollenRegion::EastMidlands),       // Buxton
+    (52.49, -1.89, PollenRegion::WestMidlands),       // Birmingham
+    (52.59, -2.13, PollenRegion::WestMidlands),       // Wolverhampton
+    (52.41, -1.51, PollenRegion::WestMidlands),       // Coventry
+    (53.00, -2.18, PollenRegion::WestMidlands),       // Stoke
+    (52.71, -2.75, PollenRegion::WestMidlands),       // Shrewsbury
+    (52.19, -2.22, PollenRegion::WestMidlands),       // Worcester
+    (52.06, -2.72, PollenRegion::WestMidlands),       // Hereford
+    (52.63, 1.30, PollenRegion::East),                // Norwich
+    (52.75, 0.40, PollenRegion::East),                // King's Lynn
+    (52.57, -0.24, PollenRegion::East),               // Peterborough
+    (52.21, 0.12, PollenRegion::East),                // Cambridge
+    (52.06, 1.16, PollenRegion::East),                // Ipswich
+    (51.90, 0.90, PollenRegion::East),                // Colchester
+    (51.74, 0.47, PollenRegion::East),                // Chelmsford
+    (51.88, -0.42, PollenRegion::East),               // Luton
+    (51.51, -0.13, PollenRegion::SouthEast),          // London
+    (51.28, 1.08, PollenRegion::SouthEast),           // Canterbury
+    (50.82, -0.14, PollenRegion::SouthEast),          // Brighton
+    (51.24, -0.57, PollenRegion::SouthEast),          // Guildford
+    (50.80, -1.09, PollenRegion::SouthEast),          // Portsmouth
+    (50.90, -1.40, PollenRegion::SouthEast),          // Southampton
+    (51.45, -0.97, PollenRegion::SouthEast),          // Reading
+    (51.75, -1.26, PollenRegion::SouthEast),          // Oxford
+    (52.04, -0.76, PollenRegion::SouthEast),          // Milton Keynes
+    (50.26, -5.05, PollenRegion::SouthWest),          // Truro
+    (50.38, -4.14, PollenRegion::SouthWest),          // Plymouth
+    (50.72, -3.53, PollenRegion::SouthWest),          // Exeter
+    (51.01, -3.10, PollenRegion::SouthWest),          // Taunton
+    (51.45, -2.59, PollenRegion::SouthWest),          // Bristol
+    (51.86, -2.24, PollenRegion::SouthWest),          // Gloucester
+    (51.56, -1.78, PollenRegion::SouthWest),          // Swindon
+    (51.07, -1.79, PollenRegion::SouthWest),          // Salisbury
+    (50.72, -1.88, PollenRegion::SouthWest),          // Bournemouth
+];
+
+impl PollenRegion {
+    /// The id of the region's element on the pollen page
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Scotland => "scotland",
+            Self::NorthernIreland => "ni",
+            Self::Wales => "wales",
+            Self::NorthEast => "ne",
+            Self::NorthWest => "nw",
+            Self::YorkshireAndHumber => "yh",
+            Self::EastMidlands => "em",
+            Self::WestMidlands => "wm",
+            Self::East => "ee",
+            Self::SouthEast => "se",
+            Self::SouthWest => "sw",
+        }
+    }
+
+    /// Finds the region for the area (the leading letters) of a UK postcode, eg "BN1 1AA" or "M1"
+    pub fn from_postcode(postcode: &str) -> Result<PollenRegion, FlowerError> {
+        let area: String = postcode
+            .trim()
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let region = match area.as_str() {
+            "AB" | "DD" | "DG" | "EH" | "FK" | "G" | "HS" | "IV" | "KA" | "KW" | "KY" | "ML"
+            | "PA" | "PH" | "TD" | "ZE" => Self::Scotland,
+            "BT" => Self::NorthernIreland,
+            "CF" | "LD" | "LL" | "NP" | "SA" => Self::Wales,
+            "DH" | "DL" | "NE" | "SR" | "TS" => Self::NorthEast,
+            "BB" | "BL" | "CA" | "CH" | "CW" | "FY" | "L" | "LA" | "M" | "OL" | "PR" | "SK"
+            | "WA" | "WN" => Self::NorthWest,
+            "BD" | "DN" | "HD" | "HG" | "HU" | "HX" | "LS" | "S" | "WF" | "YO" => {
+                Self::YorkshireAndHumber
+            }
+            "DE" | "LE" | "LN" | "NG" | "NN" => Self::EastMidlands,
+            "B" | "CV" | "DY" | "HR" | "ST" | "SY" | "TF" | "WR" | "WS" | "WV" => {
+                Self::WestMidlands
+            }
+            "AL" | "CB" | "CM" | "CO" | "EN" | "HP" | "IP" | "LU" | "NR" | "PE" | "SG" | "SS"
+            | "WD" => Self::East,
+            "BN" | "BR" | "CR" | "CT" | "DA" | "E" | "EC" | "GU" | "HA" | "IG" | "KT" | "ME"
+            | "MK" | "N" | "NW" | "OX" | "PO" | "RG" | "RH" | "RM" | "SE" | "SL" | "SM" | "SO"
+            | "SW" | "TN" | "TW" | "UB" | "W" | "WC" => Self::SouthEast,
+            "BA" | "BH" | "BS" | "DT" | "EX" | "GL" | "PL" | "SN" | "SP" | "TA" | "TQ" | "TR" => {
+                Self::SouthWest
+            }
+            _ => {
+                return Err(FlowerError::ConfigError(format!(
+                    "no pollen region for postcode {:?}",
+                    postcode
+                )))
+            }
+        };
+        Ok(region)
+    }
+
+    /// Finds the region a coordinate is in. This is only approximate near the borders between
+    /// regions, a postcode is more reliable.
+    pub fn from_coordinates(latitude: f64, longitude: f64) -> Result<PollenRegion, FlowerError> {
+        if !(49.0..=61.0).contains(&latitude) || !(-9.0..=2.0).contains(&longitude) {
+            return Err(FlowerError::ConfigError(format!(
+                "{}, {} is not in the UK",
+                latitude, longitude
+            )));
+        }
+        let distance = |(town_latitude, town_longitude, _): &&(f64, f64, PollenRegion)| {
+            let x = (longitude - town_longitude) * latitude.to_radians().cos();
+            let y = latitude - town_latitude;
+            x * x + y * y
+        };
+        let (_, _, region) = TOWNS
+            .iter()
+            .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+            .unwrap();
+        Ok(*region)
+    }
+}
+
+impl fmt::Display for PollenRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl FromStr for PollenRegion {
+    type Err = FlowerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.trim().trim_start_matches('#').to_ascii_lowercase();
+        ALL_REGIONS
+            .iter()
+            .copied()
+            .find(|region| region.id() == id)
+            .ok_or_else(|| {
+                let ids: Vec<&str> = ALL_REGIONS.iter().map(|region| region.id()).collect();
+                FlowerError::ConfigError(format!(
+                    "unknown pollen region {:?}, expected one of {}",
+                    s,
+                    ids.join(", ")
+                ))
+            })
+    }
+}
+
+impl TryFrom<String> for PollenRegion {
+    type Error = FlowerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_region_parses_from_its_id() {
+        for region in ALL_REGIONS.iter() {
+            assert_eq!(region.id().parse::<PollenRegion>().unwrap(), *region);
+        }
+        assert_eq!(
+            "#NW".parse::<PollenRegion>().unwrap(),
+            PollenRegion::NorthWest
+        );
+        assert!("narnia".parse::<PollenRegion>().is_err());
+    }
+
+    #[test]
+    fn postcodes_map_to_regions() {
+        assert_eq!(
+            PollenRegion::from_postcode("BN1 1AA").unwrap(),
+            PollenRegion::SouthEast
+        );
+        assert_eq!(
+            PollenRegion::from_postcode("m1").unwrap(),
+            PollenRegion::NorthWest
+        );
+        assert_eq!(
+            PollenRegion::from_postcode("NW1").unwrap(),
+            PollenRegion::SouthEast
+        );
+        assert_eq!(
+            PollenRegion::from_postcode("EH1").unwrap(),
+            PollenRegion::Scotland
+        );
+        assert_eq!(
+            PollenRegion::from_postcode("BT7").unwrap(),
+            PollenRegion::NorthernIreland
+        );
+        assert_eq!(
+            PollenRegion::from_postcode("CF10").unwrap(),
+            PollenRegion::Wales
+        );
+        assert!(PollenRegion::from_postcode("XX1").is_err());
+    }
+
+    #[test]
+    fn coordinates_map_to_the_nearest_region() {
+        // Hastings, Wigan, Perth, Ballymena, Merthyr Tydfil, Bath, Ely, Harrogate
+        let cases = [
+            (50.86, 0.57, PollenRegion::SouthEast),
+            (53.55, -2.63, PollenRegion::NorthWest),
+            (56.40, -3.43, PollenRegion::Scotland),
+            (54.86, -6.27, PollenRegion::NorthernIreland),
+            (51.75, -3.38, PollenRegion::Wales),
+            (51.38, -2.36, PollenRegion::SouthWest),
+            (52.40, 0.26, PollenRegion::East),
+            (53.99, -1.54, PollenRegion::YorkshireAndHumber),
+        ];
+        for (latitude, longitude, region) in cases.iter() {
+            assert_eq!(
+                PollenRegion::from_coordinates(*latitude, *longitude).unwrap(),
+                *region
+            );
+        }
+        assert!(PollenRegion::from_coordinates(48.85, 2.35).is_err());
+    }
+}
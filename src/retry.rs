@@ -0,0 +1,182 @@
+use crate::clock::Sleeper;
+use crate::error::Result;
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// How many times to try something and how long to wait in between. Each wait is `multiplier`
+/// times longer than the last, up to `max_delay_secs`, and then shortened by a random amount
+/// of up to `jitter` (0.0 to 1.0) of itself so that retries don't fall into step.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay_secs: u64,
+    pub max_delay_secs: u64,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 6,
+            initial_delay_secs: 30,
+            max_delay_secs: 600,
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The longest wait after the given failed attempt, counting from 1
+    pub fn max_delay(&self, attempt: u32) -> Duration {
+        let delay = self.initial_delay_secs as f64 * self.multiplier.powi(attempt as i32 - 1);
+        Duration::from_secs_f64(delay.min(self.max_delay_secs as f64))
+    }
+
+    /// The wait after the given failed attempt, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let random: f64 = rand::thread_rng().gen();
+        self.max_delay(attempt).mul_f64(1.0 - self.jitter * random)
+    }
+
+    /// Problems with the policy, for config validation
+    pub fn problems(&self, name: &str) -> Vec<String> {
+        let mut problems = vec![];
+        if self.max_attempts == 0 {
+            problems.push(format!("{}.max_attempts must be at least 1", name));
+        }
+        if self.initial_delay_secs > self.max_delay_secs {
+            problems.push(format!(
+                "{}.initial_delay_secs must not be more than {}.max_delay_secs",
+                name, name
+            ));
+        }
+        if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            problems.push(format!("{}.multiplier must be at least 1.0", name));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            problems.push(format!("{}.jitter must be between 0.0 and 1.0", name));
+        }
+        problems
+    }
+}
+
+/// Runs `operation` until it succeeds or the policy runs out of attempts, in which case the
+/// last error is returned. Gives up early if a wait is cut short.
+pub fn retry<T, F>(policy: &RetryPolicy, sleeper: &dyn Sleeper, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= policy.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = policy.delay(attempt);
+                eprintln!(
+                    "Attempt {} of {} failed, retrying in {}s: {}",
+                    attempt,
+                    policy.max_attempts,
+                    delay.as_secs(),
+                    e
+                );
+                sleeper.sleep(delay)?;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FakeTimeSource, TimeSource};
+    use crate::error::FlowerError;
+    use crate::pollen::{FallbackProvider, PollenCount, PollenForecast, PollenProvider};
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails a set number of times before answering
+    struct FlakyProvider {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl PollenProvider for FlakyProvider {
+        fn name(&self) -> String {
+            "flaky".to_string()
+        }
+
+        fn forecast(&self) -> Result<PollenForecast> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(FlowerError::SimpleError("no wifi".to_string()).into())
+            } else {
+                Ok(vec![(NaiveDate::from_ymd(2020, 6, 16), PollenCount::High)])
+            }
+        }
+    }
+
+    fn start() -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd(2020, 6, 16).and_hms(6, 0, 0)
+    }
+
+    fn no_jitter() -> RetryPolicy {
+        RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn delays_grow_then_stop_growing() {
+        let delays: Vec<u64> = (1..=7)
+            .map(|attempt| no_jitter().delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 600, 600]);
+    }
+
+    #[test]
+    fn jitter_only_shortens_delays() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay >= Duration::from_secs(30) && delay <= Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn a_flaky_provider_recovers_in_minutes() {
+        let clock = FakeTimeSource::new(start());
+        let provider = FallbackProvider::new(vec![Box::new(FlakyProvider {
+            failures: 3,
+            calls: AtomicU32::new(0),
+        })]);
+        let forecast = retry(&no_jitter(), &clock, || provider.forecast()).unwrap();
+        assert_eq!(forecast[0].1, PollenCount::High);
+        // 30 + 60 + 120 seconds
+        assert_eq!(clock.now() - start(), chrono::Duration::seconds(210));
+    }
+
+    #[test]
+    fn retrying_gives_up_after_the_last_attempt() {
+        let clock = FakeTimeSource::new(start());
+        let provider = FlakyProvider {
+            failures: 10,
+            calls: AtomicU32::new(0),
+        };
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..no_jitter()
+        };
+        assert!(retry(&policy, &clock, || provider.forecast()).is_err());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(clock.now() - start(), chrono::Duration::seconds(90));
+    }
+}
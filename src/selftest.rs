@@ -0,0 +1,47 @@
+use crate::cli::{Cli, SelfTestOptions};
+use crate::config::Config;
+use crate::error::Result;
+use crate::led::test_pattern::{self, TestPattern, ALL_PATTERNS};
+use crate::led::{LedBackend, LedInterface, SpiBackend, TerminalBackend};
+use crate::signal::Signal;
+use crate::supervisor::Supervisor;
+use crate::SHUTDOWN_TIMEOUT;
+use std::time::Duration;
+
+/// Runs the LED test patterns for `flower selftest`, stopping early on any signal
+pub fn run(cli: &Cli, config: &Config, options: &SelfTestOptions) -> Result<()> {
+    let backend: Box<dyn LedBackend> = if cli.simulate {
+        Box::new(TerminalBackend::new(config.leds.offset)?)
+    } else {
+        Box::new(SpiBackend::new(&config.spi, &config.leds)?)
+    };
+    let mut interface = LedInterface::new(config.leds.count, backend)?;
+    let mut supervisor = Supervisor::new();
+    let interrupt = Signal::get_receiver(&mut supervisor)?;
+
+    let patterns = if options.patterns.is_empty() {
+        ALL_PATTERNS.to_vec()
+    } else {
+        options.patterns.clone()
+    };
+    let step = options.step_ms.map(Duration::from_millis);
+    for pattern in patterns.iter() {
+        println!("Showing {}", pattern);
+        let frames = pattern.frames(config.leds.count, step)?;
+        if test_pattern::play(&mut interface, &frames, &interrupt)?.is_some() {
+            println!("Stopped");
+            break;
+        }
+    }
+    if patterns.contains(&TestPattern::Chase) {
+        println!(
+            "Set leds.offset to the LED at the top of the ring, it is currently {}",
+            config.leds.offset
+        );
+    }
+
+    for failure in supervisor.shutdown(SHUTDOWN_TIMEOUT) {
+        eprintln!("{}", failure);
+    }
+    Ok(())
+}
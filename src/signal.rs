@@ -1,8 +1,9 @@
+use crate::error::Result;
+use crate::supervisor::Supervisor;
 use core::fmt;
 use crossbeam_channel::{bounded, Receiver};
 use signal_hook::iterator::Signals;
 use signal_hook::{SIGALRM, SIGHUP, SIGINT, SIGPIPE, SIGPROF, SIGTERM, SIGUSR1, SIGUSR2};
-use std::thread;
 
 pub struct Signal(i32);
 
@@ -22,26 +23,89 @@ impl fmt::Display for Signal {
     }
 }
 
+/// What a signal asks the flower to do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignalRequest {
+    /// SIGTERM or SIGINT
+    Shutdown,
+    /// SIGHUP, re-read the config file and fetch the pollen forecast again
+    Reload,
+    /// SIGUSR1, write the current state to the log
+    DumpState,
+    /// SIGUSR2, show the LED test pattern
+    SelfTest,
+    /// SIGALRM, switch to the next display mode
+    NextMode,
+}
+
+impl SignalRequest {
+    pub fn from_signal(signal: i32) -> Option<SignalRequest> {
+        match signal {
+            SIGTERM | SIGINT => Some(Self::Shutdown),
+            SIGHUP => Some(Self::Reload),
+            SIGUSR1 => Some(Self::DumpState),
+            SIGUSR2 => Some(Self::SelfTest),
+            SIGALRM => Some(Self::NextMode),
+            _ => None,
+        }
+    }
+}
+
 impl Signal {
-    pub fn get_exit_receiver() -> Receiver<i32> {
-        // Warning: This process is immediately orphaned
-        let (signal_sender, signal_receiver) = bounded::<i32>(10);
-        thread::spawn(move || {
-            let signals = Signals::new(&[
-                SIGALRM, SIGHUP, SIGINT, SIGPIPE, SIGPROF, SIGTERM, SIGUSR1, SIGUSR2,
-            ])
-            .unwrap();
+    pub fn get_receiver(supervisor: &mut Supervisor) -> Result<Receiver<SignalRequest>> {
+        let (signal_sender, signal_receiver) = bounded::<SignalRequest>(10);
+        let signals = Signals::new([SIGALRM, SIGHUP, SIGINT, SIGTERM, SIGUSR1, SIGUSR2])?;
+        // Waiting for a signal can't watch the stop signal, closing ends the wait instead
+        let closer = signals.clone();
+        supervisor.on_stop(move || closer.close());
+        supervisor.spawn("signals", move |_| {
             for signal in signals.forever() {
-                match signal {
-                    SIGALRM | SIGHUP | SIGINT | SIGPIPE | SIGPROF | SIGTERM | SIGUSR1 | SIGUSR2 => {
-                        println!("Received {}", Signal(signal));
-                        break;
+                println!("Received {}", Signal(signal));
+                match SignalRequest::from_signal(signal) {
+                    Some(request) => {
+                        if signal_sender.send(request).is_err() {
+                            break; // Nobody is listening any more
+                        }
                     }
-                    _ => println!("unknown signal received"),
+                    None => println!("unknown signal received"),
                 }
-                let _ = signal_sender.send(signal); // We're quitting now, not a lot else to do
             }
-        });
-        signal_receiver
+            Ok(())
+        })?;
+        Ok(signal_receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_signal_has_its_own_meaning() {
+        assert_eq!(
+            SignalRequest::from_signal(SIGTERM),
+            Some(SignalRequest::Shutdown)
+        );
+        assert_eq!(
+            SignalRequest::from_signal(SIGINT),
+            Some(SignalRequest::Shutdown)
+        );
+        assert_eq!(
+            SignalRequest::from_signal(SIGHUP),
+            Some(SignalRequest::Reload)
+        );
+        assert_eq!(
+            SignalRequest::from_signal(SIGUSR1),
+            Some(SignalRequest::DumpState)
+        );
+        assert_eq!(
+            SignalRequest::from_signal(SIGUSR2),
+            Some(SignalRequest::SelfTest)
+        );
+        assert_eq!(
+            SignalRequest::from_signal(SIGALRM),
+            Some(SignalRequest::NextMode)
+        );
+        assert_eq!(SignalRequest::from_signal(SIGPIPE), None);
     }
 }
@@ -0,0 +1,298 @@
+use crate::clock::Sleeper;
+use crate::error::{FlowerError, Result};
+use crossbeam_channel::{bounded, select, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::any::Any;
+use std::fmt;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Lets a worker know it should finish up. Nothing is ever sent, the channel is disconnected
+/// when the supervisor stops so every clone sees it at once. A worker that can be stopped on
+/// its own also watches a channel of its own.
+#[derive(Clone)]
+pub struct StopSignal {
+    receiver: Receiver<()>,
+    own: Option<Receiver<()>>,
+}
+
+impl StopSignal {
+    pub fn is_stopped(&self) -> bool {
+        let stopped = |receiver: &Receiver<()>| {
+            matches!(receiver.try_recv(), Err(TryRecvError::Disconnected))
+        };
+        stopped(&self.receiver) || self.own.as_ref().is_some_and(stopped)
+    }
+}
+
+/// Sleeping is cut short by a stop
+impl Sleeper for StopSignal {
+    fn sleep(&self, duration: Duration) -> Result<()> {
+        let own = match &self.own {
+            Some(own) => own,
+            None => {
+                return match self.receiver.recv_timeout(duration) {
+                    Err(RecvTimeoutError::Timeout) => Ok(()),
+                    _ => Err(FlowerError::Stopped.into()),
+                }
+            }
+        };
+        select! {
+            recv(self.receiver) -> _ => Err(FlowerError::Stopped.into()),
+            recv(own) -> _ => Err(FlowerError::Stopped.into()),
+            default(duration) => Ok(()),
+        }
+    }
+}
+
+/// Stops a single worker when it is dropped, or replaced by the stop for the next one
+pub struct WorkerStop {
+    _sender: Sender<()>,
+}
+
+/// Why a worker didn't finish cleanly
+#[derive(Debug)]
+pub enum WorkerFailure {
+    Failed(String, anyhow::Error),
+    Panicked(String, String),
+    TimedOut(String),
+}
+
+impl fmt::Display for WorkerFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(name, e) => write!(f, "Worker {} failed: {}", name, e),
+            Self::Panicked(name, message) => write!(f, "Worker {} panicked: {}", name, message),
+            Self::TimedOut(name) => write!(f, "Worker {} did not stop in time", name),
+        }
+    }
+}
+
+struct Worker {
+    name: String,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl Worker {
+    fn join(self) -> Option<WorkerFailure> {
+        match self.handle.join() {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(WorkerFailure::Failed(self.name, e)),
+            Err(panic) => Some(WorkerFailure::Panicked(self.name, panic_message(panic))),
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Owns the background threads so they can be told to stop and be joined
+pub struct Supervisor {
+    stop_sender: Option<Sender<()>>,
+    stop: StopSignal,
+    on_stop: Vec<Box<dyn FnOnce() + Send>>,
+    workers: Vec<Worker>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Supervisor {
+        let (stop_sender, receiver) = bounded(0);
+        Supervisor {
+            stop_sender: Some(stop_sender),
+            stop: StopSignal {
+                receiver,
+                own: None,
+            },
+            on_stop: vec![],
+            workers: vec![],
+        }
+    }
+
+    /// Starts `work` on its own thread, handing it a signal to watch for stopping
+    pub fn spawn<F>(&mut self, name: &str, work: F) -> Result<()>
+    where
+        F: FnOnce(StopSignal) -> Result<()> + Send + 'static,
+    {
+        let stop = self.stop.clone();
+        self.spawn_with(name, stop, work)
+    }
+
+    /// Starts `work` like `spawn`, but it can also be stopped without stopping the others
+    pub fn spawn_stoppable<F>(&mut self, name: &str, work: F) -> Result<WorkerStop>
+    where
+        F: FnOnce(StopSignal) -> Result<()> + Send + 'static,
+    {
+        let (sender, own) = bounded(0);
+        let stop = StopSignal {
+            own: Some(own),
+            ..self.stop.clone()
+        };
+        self.spawn_with(name, stop, work)?;
+        Ok(WorkerStop { _sender: sender })
+    }
+
+    fn spawn_with<F>(&mut self, name: &str, stop: StopSignal, work: F) -> Result<()>
+    where
+        F: FnOnce(StopSignal) -> Result<()> + Send + 'static,
+    {
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || work(stop))?;
+        self.workers.push(Worker {
+            name: name.to_string(),
+            handle,
+        });
+        Ok(())
+    }
+
+    /// Runs `hook` when stopping, for workers stuck in a call that can't watch the stop signal
+    pub fn on_stop<F>(&mut self, hook: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_stop.push(Box::new(hook));
+    }
+
+    /// The names of the workers that haven't been joined yet
+    pub fn running(&self) -> Vec<&str> {
+        self.workers
+            .iter()
+            .map(|worker| worker.name.as_str())
+            .collect()
+    }
+
+    /// Joins the workers that have already finished, returning any that failed
+    pub fn reap(&mut self) -> Vec<WorkerFailure> {
+        let (finished, running) = self
+            .workers
+            .drain(..)
+            .partition(|worker| worker.handle.is_finished());
+        self.workers = running;
+        finished.into_iter().filter_map(Worker::join).collect()
+    }
+
+    /// Tells every worker to stop and waits up to `timeout` for them, returning any that
+    /// failed or are still running. Workers still running are left behind.
+    pub fn shutdown(mut self, timeout: Duration) -> Vec<WorkerFailure> {
+        self.stop_sender = None;
+        for hook in self.on_stop.drain(..) {
+            hook();
+        }
+        let deadline = Instant::now() + timeout;
+        let mut failures = vec![];
+        while !self.workers.is_empty() && Instant::now() < deadline {
+            failures.extend(self.reap());
+            thread::sleep(Duration::from_millis(10));
+        }
+        failures.extend(self.reap());
+        failures.extend(
+            self.workers
+                .drain(..)
+                .map(|worker| WorkerFailure::TimedOut(worker.name)),
+        );
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workers_are_stopped_and_joined() {
+        let mut supervisor = Supervisor::new();
+        supervisor
+            .spawn("polling", |stop| {
+                while !stop.is_stopped() {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Ok(())
+            })
+            .unwrap();
+        supervisor
+            .spawn("sleeping", |stop| {
+                assert!(stop.sleep(Duration::from_secs(60)).is_err());
+                Ok(())
+            })
+            .unwrap();
+        let started = Instant::now();
+        assert!(supervisor.shutdown(Duration::from_secs(5)).is_empty());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn a_worker_can_be_stopped_on_its_own() {
+        let mut supervisor = Supervisor::new();
+        let (sender, receiver) = bounded(2);
+        let mut spawn = |name: &str| {
+            let sender = sender.clone();
+            let name = name.to_string();
+            supervisor.spawn_stoppable(&name.clone(), move |stop| {
+                let _ = stop.sleep(Duration::from_secs(60));
+                sender.send(name).unwrap();
+                Ok(())
+            })
+        };
+        let first = spawn("first").unwrap();
+        let _second = spawn("second").unwrap();
+        drop(first);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Ok("first".to_string())
+        );
+        assert!(receiver.try_recv().is_err());
+        assert!(supervisor.shutdown(Duration::from_secs(5)).is_empty());
+        assert_eq!(receiver.try_recv(), Ok("second".to_string()));
+    }
+
+    #[test]
+    fn panics_errors_and_stragglers_are_reported() {
+        let mut supervisor = Supervisor::new();
+        supervisor
+            .spawn("panicking", |_| panic!("led on fire"))
+            .unwrap();
+        supervisor
+            .spawn("failing", |_| {
+                Err(FlowerError::SimpleError("no wifi".to_string()).into())
+            })
+            .unwrap();
+        supervisor
+            .spawn("stubborn", |_| {
+                thread::sleep(Duration::from_millis(500));
+                Ok(())
+            })
+            .unwrap();
+        let failures: Vec<String> = supervisor
+            .shutdown(Duration::from_millis(100))
+            .iter()
+            .map(|failure| failure.to_string())
+            .collect();
+        assert_eq!(failures.len(), 3, "{:?}", failures);
+        assert!(failures.contains(&"Worker panicking panicked: led on fire".to_string()));
+        assert!(failures
+            .iter()
+            .any(|f| f.starts_with("Worker failing failed")));
+        assert!(failures.contains(&"Worker stubborn did not stop in time".to_string()));
+    }
+
+    #[test]
+    fn stop_hooks_run_on_shutdown() {
+        let mut supervisor = Supervisor::new();
+        let (sender, receiver) = bounded(1);
+        supervisor.on_stop(move || sender.send(()).unwrap());
+        supervisor.shutdown(Duration::from_secs(1));
+        assert!(receiver.try_recv().is_ok());
+    }
+}